@@ -17,8 +17,12 @@ struct Counter {
 
 impl Counter {
     fn count<R: BufRead>(&mut self, reader: R) -> Result<&mut Counter, std::io::Error> {
-        for line in reader.lines() {
+        // Split on raw bytes instead of using `lines()`, which fails on
+        // invalid UTF-8. Invalid sequences become U+FFFD instead.
+        for line in reader.split(b'\n') {
             let line = line?;
+            let line = String::from_utf8_lossy(&line);
+            let line = line.strip_suffix('\r').unwrap_or(&line);
             self.lines += 1;
             self.words += line.split_whitespace().count();
             self.chars += line.chars().count();
@@ -1,5 +1,5 @@
 use criterion::{criterion_group, criterion_main, Criterion};
-use std::{fs::File, hint::black_box, io::Read};
+use std::{fs::File, hint::black_box};
 use wc::counter;
 
 fn criterion_benchmark(c: &mut Criterion) {
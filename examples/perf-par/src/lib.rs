@@ -1,13 +1,27 @@
+use perf::metric::{Bytes, Chars, Chunk, Graphemes, Lines, Metric};
+pub use perf::WordBoundary;
+use perf::{Paragraphs, Sentences, Words};
 use rayon::prelude::*;
 use std::io::{BufReader, Read};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Counts for words, lines, and characters
+///
+/// "Characters" come in three flavors, which only agree on ASCII text:
+/// raw bytes (`wc -c`), Unicode scalar values (`wc -m`) and extended
+/// grapheme clusters, i.e. what a reader perceives as a single character.
 #[derive(Debug, Default, Clone, Copy)]
 pub struct Counts {
     pub words: usize,
     pub lines: usize,
-    pub characters: usize,
+    /// Sentences, which end with terminal punctuation or a paragraph break
+    pub sentences: usize,
+    /// Runs of lines with text, separated by blank lines
+    pub paragraphs: usize,
+    pub bytes: usize,
+    pub chars: usize,
+    pub graphemes: usize,
 }
 
 impl std::ops::Add for Counts {
@@ -16,7 +30,11 @@ impl std::ops::Add for Counts {
         Self {
             words: self.words + other.words,
             lines: self.lines + other.lines,
-            characters: self.characters + other.characters,
+            sentences: self.sentences + other.sentences,
+            paragraphs: self.paragraphs + other.paragraphs,
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            graphemes: self.graphemes + other.graphemes,
         }
     }
 }
@@ -27,55 +45,286 @@ impl std::ops::AddAssign for Counts {
     }
 }
 
-fn count_chunk(chunk: &[u8]) -> Counts {
-    let mut counts = Counts::default();
-    let mut in_word = false;
+/// Options for counting
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// What separates one word from the next, the same way as in `perf-wc`
+    pub word_boundary: WordBoundary,
+    /// Decode the input as UTF-8 to count characters and grapheme clusters.
+    /// Without it, only bytes, lines, and words are counted, which is a lot
+    /// faster. Non-ASCII word boundaries always decode the input.
+    pub decode: bool,
+    /// Count sentences and paragraphs, which takes another look at every
+    /// character of the decoded input
+    pub prose: bool,
+}
 
-    for &byte in chunk {
-        counts.characters += 1;
-        match byte {
-            b'\n' => {
-                counts.lines += 1;
-                in_word = false;
-            }
-            _ if byte.is_ascii_whitespace() => in_word = false,
-            _ => {
-                if !in_word {
-                    counts.words += 1;
-                    in_word = true;
-                }
-            }
+/// Size of the slices the input is split into for parallel counting
+const CHUNK_SIZE: usize = 1024 * 1024; // 1MB
+
+/// The metrics of one slice of the input, or of several in a row
+///
+/// Each [merges](Metric::merge) with the same metric of the slice that
+/// directly follows, which is associative and has an empty `Slice` as the
+/// identity. So slices can be counted in any grouping and still add up
+/// to the counts of the whole input.
+#[derive(Debug, Clone)]
+struct Slice {
+    bytes: Bytes,
+    lines: Lines,
+    words: Words,
+    chars: Chars,
+    graphemes: Graphemes,
+    sentences: Sentences,
+    paragraphs: Paragraphs,
+}
+
+impl Slice {
+    fn new(options: &Options) -> Self {
+        Self {
+            bytes: Bytes::default(),
+            lines: Lines::default(),
+            words: Words::new(options.word_boundary),
+            chars: Chars::default(),
+            graphemes: Graphemes::default(),
+            sentences: Sentences::default(),
+            paragraphs: Paragraphs::default(),
+        }
+    }
+
+    /// Count a slice of the input on its own
+    fn count(raw: &[u8], options: &Options) -> Self {
+        let mut slice = Self::new(options);
+        let needs_text = options.decode || options.prose || slice.words.needs_text();
+        let text = needs_text.then(|| String::from_utf8_lossy(raw));
+        let chunk = Chunk {
+            raw,
+            bytes: raw,
+            text: text.as_deref(),
+        };
+        slice.bytes.update(&chunk);
+        match slice.words.update_counting_lines(&chunk) {
+            Some(newlines) => slice.lines.update_counted(&chunk, newlines),
+            None => slice.lines.update(&chunk),
+        }
+        slice.chars.update(&chunk);
+        slice.graphemes.update(&chunk);
+        if options.prose {
+            slice.sentences.update(&chunk);
+            slice.paragraphs.update(&chunk);
+        }
+        slice
+    }
+
+    /// Merge with the slice that directly follows this one
+    fn merge(mut self, next: Self) -> Self {
+        self.bytes.merge(next.bytes);
+        self.lines.merge(next.lines);
+        self.words.merge(next.words);
+        self.chars.merge(next.chars);
+        self.graphemes.merge(next.graphemes);
+        self.sentences.merge(next.sentences);
+        self.paragraphs.merge(next.paragraphs);
+        self
+    }
+
+    /// Final counts once all slices of the input are merged
+    fn finish(mut self) -> Counts {
+        self.lines.finish();
+        self.words.finish();
+        self.graphemes.finish();
+        self.sentences.finish();
+        self.paragraphs.finish();
+        Counts {
+            words: self.words.count(),
+            lines: self.lines.count(),
+            sentences: self.sentences.count(),
+            paragraphs: self.paragraphs.count(),
+            bytes: self.bytes.count(),
+            chars: self.chars.count(),
+            graphemes: self.graphemes.count(),
         }
     }
+}
 
-    counts
+/// Whether a slice can start at `index` without cutting a UTF-8 sequence
+/// in half, valid or not: it doesn't start with a continuation byte, or
+/// there's no lead byte close enough before it that it could belong to
+fn is_sequence_boundary(bytes: &[u8], index: usize) -> bool {
+    let is_continuation = |byte: &u8| (0x80..0xC0).contains(byte);
+    !is_continuation(&bytes[index])
+        || bytes[index.saturating_sub(3)..index]
+            .iter()
+            .all(is_continuation)
+}
+
+/// Split `bytes` into slices of about `chunk_size` bytes
+///
+/// Slices end right after a newline where possible. Otherwise they end
+/// between UTF-8 sequences, so each slice decodes to the same text on its
+/// own as it does as part of the whole input.
+fn split(bytes: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    let mut slices = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let end = (chunk_size..rest.len())
+            .find(|&i| is_sequence_boundary(rest, i))
+            .unwrap_or(rest.len());
+        let end = match rest[..end].iter().rposition(|&byte| byte == b'\n') {
+            Some(newline) if end < rest.len() => newline + 1,
+            _ => end,
+        };
+        let (slice, tail) = rest.split_at(end);
+        slices.push(slice);
+        rest = tail;
+    }
+    slices
 }
 
+/// Count `bytes` by splitting them into `chunk_size` slices counted in
+/// parallel
+fn count_chunks(bytes: &[u8], chunk_size: usize, options: &Options) -> Counts {
+    split(bytes, chunk_size)
+        .into_par_iter()
+        .map(|slice| Slice::count(slice, options))
+        .reduce(|| Slice::new(options), Slice::merge)
+        .finish()
+}
+
+/// Count words, lines, and bytes, like `wc` without any flags
+///
+/// Characters, sentences, and paragraphs are counted with [`Options`] that
+/// turn them on.
 pub fn counter(input: impl Read) -> Result<Counts> {
-    let mut reader = BufReader::with_capacity(1024 * 1024, input); // 1MB buffer
+    counter_with(input, &Options::default())
+}
+
+/// Count words, lines, and characters with the given options
+///
+/// Invalid UTF-8 sequences count as one U+FFFD replacement character each,
+/// but as the bytes they are.
+pub fn counter_with(input: impl Read, options: &Options) -> Result<Counts> {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, input);
     let mut buffer = Vec::new();
     reader.read_to_end(&mut buffer)?;
 
-    // Split the buffer into chunks of about 1MB each
-    let chunk_size = 1024 * 1024;
-    let counts: Counts = buffer
-        .par_chunks(chunk_size)
-        .map(count_chunk)
-        .reduce(|| Counts::default(), |a, b| a + b);
-
-    Ok(counts)
+    Ok(count_chunks(&buffer, CHUNK_SIZE, options))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Options that count everything
+    fn everything() -> Options {
+        Options {
+            decode: true,
+            prose: true,
+            ..Options::default()
+        }
+    }
+
     #[test]
     fn test_counter() {
         let input = "Hello, World!\nThis is a test.";
-        let counts = counter(input.as_bytes()).unwrap();
-        assert_eq!(counts.words, 5);
+        let counts = counter_with(input.as_bytes(), &everything()).unwrap();
+        assert_eq!(counts.words, 6);
         assert_eq!(counts.lines, 2);
-        assert_eq!(counts.characters, 28);
+        assert_eq!(counts.bytes, 29);
+        assert_eq!(counts.chars, 29);
+        assert_eq!(counts.graphemes, 29);
+        assert_eq!(counts.sentences, 2);
+
+        // Nothing is decoded unless asked for
+        let counts = counter(input.as_bytes()).unwrap();
+        assert_eq!(counts.words, 6);
+        assert_eq!(counts.bytes, 29);
+        assert_eq!(counts.chars, 0);
+        assert_eq!(counts.sentences, 0);
+    }
+
+    #[test]
+    fn test_chunk_size_does_not_matter() {
+        let input = "Hello, World!\nThis  is\ta test.\n\nwith  trailing words  ";
+        let expected = Slice::count(input.as_bytes(), &everything()).finish();
+        for chunk_size in 1..=input.len() {
+            let counts = count_chunks(input.as_bytes(), chunk_size, &everything());
+            assert_eq!(counts.words, expected.words, "chunk size {chunk_size}");
+            assert_eq!(counts.lines, expected.lines, "chunk size {chunk_size}");
+            assert_eq!(counts.bytes, expected.bytes);
+            assert_eq!(
+                counts.sentences, expected.sentences,
+                "chunk size {chunk_size}"
+            );
+            assert_eq!(
+                counts.paragraphs, expected.paragraphs,
+                "chunk size {chunk_size}"
+            );
+        }
+        assert_eq!(expected.words, 9);
+        assert_eq!(expected.lines, 4);
+    }
+
+    #[test]
+    fn test_count_bytes_chars_graphemes() {
+        // "é" is written as "e" + combining acute accent; each flag is two
+        // regional indicators; "\r\n" is a single grapheme cluster
+        let input = "naïve cafe\u{301} 🇩🇪🇫🇷🇮🇹\r\n日本語";
+        for chunk_size in 1..=input.len() {
+            let counts = count_chunks(input.as_bytes(), chunk_size, &everything());
+            assert_eq!(counts.bytes, 49, "chunk size {chunk_size}");
+            assert_eq!(counts.chars, 23, "chunk size {chunk_size}");
+            assert_eq!(counts.graphemes, 18, "chunk size {chunk_size}");
+            assert_eq!(counts.words, 4, "chunk size {chunk_size}");
+            assert_eq!(counts.lines, 2, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        // No-break space, ideographic space, and an apostrophe; the same
+        // input and counts as in perf-wc
+        let input = "one\u{a0}two\u{3000}three can't stop, 日本語";
+        let count = |word_boundary| {
+            let options = Options {
+                word_boundary,
+                ..Options::default()
+            };
+            let expected = counter_with(input.as_bytes(), &options).unwrap().words;
+            for chunk_size in 1..=input.len() {
+                let counts = count_chunks(input.as_bytes(), chunk_size, &options);
+                assert_eq!(counts.words, expected, "{word_boundary:?} {chunk_size}");
+            }
+            expected
+        };
+        assert_eq!(count(WordBoundary::Ascii), 4);
+        assert_eq!(count(WordBoundary::Unicode), 6);
+        assert_eq!(count(WordBoundary::Uax29), 8);
+    }
+
+    #[test]
+    fn test_sentences_and_paragraphs() {
+        let input = "CHAPTER I\r\n\r\n  Mr. Smith said: \"Hi!\"  It's 3.14, e.g. pi.\n\
+                     \n\n\nThe end";
+        for chunk_size in 1..=input.len() {
+            let counts = count_chunks(input.as_bytes(), chunk_size, &everything());
+            assert_eq!(counts.sentences, 4, "chunk size {chunk_size}");
+            assert_eq!(counts.paragraphs, 3, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        // A lone Latin-1 "é", the Euro sign with a stray continuation byte,
+        // a cut-off sequence, and two bytes that are never valid
+        let input = b"caf\xe9 au lait \xe2\x82\xac\x80\n\xe2\x82 \xff\xfe";
+        for chunk_size in 1..=input.len() {
+            let counts = count_chunks(input, chunk_size, &everything());
+            assert_eq!(counts.bytes, 23, "chunk size {chunk_size}");
+            assert_eq!(counts.chars, 20, "chunk size {chunk_size}");
+            assert_eq!(counts.graphemes, 20, "chunk size {chunk_size}");
+            assert_eq!(counts.words, 6, "chunk size {chunk_size}");
+            assert_eq!(counts.lines, 2, "chunk size {chunk_size}");
+        }
     }
 }
@@ -4,47 +4,132 @@
 #![warn(clippy::nursery)]
 #![warn(clippy::cargo)]
 
-use wc::{counter, Counts};
+use wc::{counter_with, Counts, Options};
 
 use std::{fs::File, io::Read};
 
 const STDIN: &str = "-";
 
+const USAGE: &str = "Usage: wc [-lspwmcg] [--words=ascii|unicode|uax29] <path>...";
+
 /// Custom error type
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
+/// Which counts to print
+///
+/// Like `wc`, the columns are always printed in the same order,
+/// no matter in which order the flags were given.
+#[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct Columns {
+    lines: bool,
+    sentences: bool,
+    paragraphs: bool,
+    words: bool,
+    chars: bool,
+    graphemes: bool,
+    bytes: bool,
+}
+
+impl Columns {
+    /// Parse flags like `-l`, `-w` or combined ones like `-lw`
+    fn parse(&mut self, flags: &str) -> Result<()> {
+        for flag in flags.chars() {
+            match flag {
+                'l' => self.lines = true,
+                's' => self.sentences = true,
+                'p' => self.paragraphs = true,
+                'w' => self.words = true,
+                'm' => self.chars = true,
+                'g' => self.graphemes = true,
+                'c' => self.bytes = true,
+                _ => return Err(format!("Unknown option: -{flag}\n{USAGE}").into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Without any flags, print lines, words, and bytes (same as `wc`)
+    const fn or_default(self) -> Self {
+        if self.lines
+            || self.sentences
+            || self.paragraphs
+            || self.words
+            || self.chars
+            || self.graphemes
+            || self.bytes
+        {
+            self
+        } else {
+            Self {
+                lines: true,
+                words: true,
+                bytes: true,
+                sentences: false,
+                paragraphs: false,
+                chars: false,
+                graphemes: false,
+            }
+        }
+    }
+
+    fn format(&self, counts: &Counts, name: &str) -> String {
+        [
+            (self.lines, counts.lines),
+            (self.sentences, counts.sentences),
+            (self.paragraphs, counts.paragraphs),
+            (self.words, counts.words),
+            (self.chars, counts.chars),
+            (self.graphemes, counts.graphemes),
+            (self.bytes, counts.bytes),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, count)| format!("{count:>7} "))
+        .chain(std::iter::once(name.to_string()))
+        .collect()
+    }
+}
+
 fn main() -> Result<()> {
-    if std::env::args().len() < 2 {
-        eprintln!("Usage: wc <path>");
+    let mut columns = Columns::default();
+    let mut options = Options::default();
+    let mut files = Vec::new();
+    for arg in std::env::args().skip(1) {
+        if let Some(word_boundary) = arg.strip_prefix("--words=") {
+            options.word_boundary = word_boundary.parse()?;
+            continue;
+        }
+        match arg.strip_prefix('-') {
+            Some(flags) if !flags.is_empty() => columns.parse(flags)?,
+            _ => files.push(arg),
+        }
+    }
+    let columns = columns.or_default();
+    options.decode = columns.chars || columns.graphemes;
+    options.prose = columns.sentences || columns.paragraphs;
+
+    if files.is_empty() {
+        eprintln!("{USAGE}");
         return Err("Could not read file".into());
     }
 
-    let mut total = Counts {
-        words: 0,
-        lines: 0,
-        characters: 0,
-    };
+    let mut total = Counts::default();
 
-    for file in std::env::args().skip(1) {
+    for file in files {
         let mut reader: Box<dyn Read> = if file == STDIN {
             Box::new(std::io::stdin())
         } else {
             Box::new(File::open(&file)?)
         };
 
-        let counts = counter(&mut reader)?;
+        let counts = counter_with(&mut reader, &options)?;
 
         total += counts;
-        println!(
-            "{:>7} {:>7} {:>7} {}",
-            counts.lines, counts.words, counts.characters, file
-        );
+        println!("{}", columns.format(&counts, &file));
     }
 
-    println!(
-        "{:>7} {:>7} {:>7} total",
-        total.lines, total.words, total.characters
-    );
+    println!("{}", columns.format(&total, "total"));
 
     Ok(())
 }
@@ -0,0 +1,22 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use wc::kernel::Kernel;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    // Generated by `create_large_text.sh` in the root of the repository
+    let input = std::fs::read("../../fixtures/large.txt").unwrap();
+
+    let mut group = c.benchmark_group("kernel");
+    group.throughput(Throughput::Bytes(input.len() as u64));
+    for kernel in Kernel::available() {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{kernel:?}")),
+            &input,
+            |b, input| b.iter(|| black_box(kernel.count(black_box(input), false))),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
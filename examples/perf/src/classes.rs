@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use unicode_properties::{
+    GeneralCategory, GeneralCategoryGroup, UnicodeEmoji, UnicodeGeneralCategory,
+};
+
+use crate::metric::{Chunk, Metric};
+
+/// Broad class of a character, mostly by its Unicode general category
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CharClass {
+    /// Letters of any script, e.g. "a", "ß" or "語"
+    Letter,
+    /// Combining marks like accents written as separate characters
+    Mark,
+    /// Decimal digits and other numerals like "½" or "Ⅻ"
+    Digit,
+    /// Punctuation, including dashes, brackets, and quotation marks
+    Punctuation,
+    /// Math and currency symbols and the like, except for emoji
+    Symbol,
+    /// Emoji, along with the characters that combine them, like zero
+    /// width joiners and skin tone modifiers
+    Emoji,
+    /// Spaces, tabs, line breaks and other whitespace
+    Whitespace,
+    /// Control and invisible format characters other than whitespace,
+    /// e.g. escape or a byte order mark
+    Control,
+    /// Private use characters, surrogates, and unassigned code points
+    Other,
+}
+
+impl CharClass {
+    /// All classes, in the order they're reported in
+    pub const ALL: [Self; 9] = [
+        Self::Letter,
+        Self::Mark,
+        Self::Digit,
+        Self::Punctuation,
+        Self::Symbol,
+        Self::Emoji,
+        Self::Whitespace,
+        Self::Control,
+        Self::Other,
+    ];
+
+    pub fn of(c: char) -> Self {
+        // Fast path: ASCII is either of these, `#` and `*` notwithstanding
+        if c.is_ascii() {
+            return match c {
+                _ if c.is_ascii_alphabetic() => Self::Letter,
+                _ if c.is_ascii_digit() => Self::Digit,
+                _ if c.is_ascii_whitespace() || c == '\x0B' => Self::Whitespace,
+                _ if c.is_ascii_control() => Self::Control,
+                '$' | '+' | '<' | '=' | '>' | '^' | '`' | '|' | '~' => Self::Symbol,
+                _ => Self::Punctuation,
+            };
+        }
+        if c.is_whitespace() {
+            return Self::Whitespace;
+        }
+        if c.is_emoji_char_or_emoji_component() {
+            return Self::Emoji;
+        }
+        match c.general_category_group() {
+            GeneralCategoryGroup::Letter => Self::Letter,
+            GeneralCategoryGroup::Mark => Self::Mark,
+            GeneralCategoryGroup::Number => Self::Digit,
+            GeneralCategoryGroup::Punctuation => Self::Punctuation,
+            GeneralCategoryGroup::Symbol => Self::Symbol,
+            GeneralCategoryGroup::Separator => Self::Whitespace,
+            GeneralCategoryGroup::Other => match c.general_category() {
+                GeneralCategory::Control | GeneralCategory::Format => Self::Control,
+                _ => Self::Other,
+            },
+        }
+    }
+
+    /// Plural name, e.g. to label a column
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Letter => "letters",
+            Self::Mark => "marks",
+            Self::Digit => "digits",
+            Self::Punctuation => "punctuation",
+            Self::Symbol => "symbols",
+            Self::Emoji => "emoji",
+            Self::Whitespace => "whitespace",
+            Self::Control => "control",
+            Self::Other => "other",
+        }
+    }
+
+    const fn index(self) -> usize {
+        match self {
+            Self::Letter => 0,
+            Self::Mark => 1,
+            Self::Digit => 2,
+            Self::Punctuation => 3,
+            Self::Symbol => 4,
+            Self::Emoji => 5,
+            Self::Whitespace => 6,
+            Self::Control => 7,
+            Self::Other => 8,
+        }
+    }
+}
+
+/// Number of characters in each [`CharClass`]
+///
+/// Characters are Unicode scalar values, like [`Counts::chars`](crate::Counts::chars),
+/// so an emoji made up of several of them counts more than once.
+/// Optionally also counts how often each code point occurs, which takes
+/// memory for every distinct one.
+#[derive(Debug, Default, Clone)]
+pub struct CharClasses {
+    counts: [usize; CharClass::ALL.len()],
+    /// Occurrences of each code point, if they are counted
+    code_points: Option<HashMap<char, usize>>,
+}
+
+impl CharClasses {
+    /// Also count how often each code point occurs
+    #[must_use]
+    pub fn with_code_points(self) -> Self {
+        Self {
+            code_points: Some(HashMap::new()),
+            ..self
+        }
+    }
+
+    /// Number of characters in the class
+    pub const fn get(&self, class: CharClass) -> usize {
+        self.counts[class.index()]
+    }
+
+    /// All code points with how often they occur, most frequent first,
+    /// or `None` if they aren't counted.
+    /// Code points that occur equally often are sorted by value.
+    pub fn code_points(&self) -> Option<Vec<(char, usize)>> {
+        let mut code_points: Vec<_> = self
+            .code_points
+            .as_ref()?
+            .iter()
+            .map(|(&c, &count)| (c, count))
+            .collect();
+        code_points.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Some(code_points)
+    }
+}
+
+impl Metric for CharClasses {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        for c in text.chars() {
+            self.counts[CharClass::of(c).index()] += 1;
+        }
+        if let Some(code_points) = self.code_points.as_mut() {
+            for c in text.chars() {
+                *code_points.entry(c).or_default() += 1;
+            }
+        }
+    }
+
+    fn merge(&mut self, next: Self) {
+        *self += next;
+    }
+}
+
+/// Add up the counts of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for CharClasses {
+    fn add_assign(&mut self, other: Self) {
+        for (count, other) in self.counts.iter_mut().zip(other.counts) {
+            *count += other;
+        }
+        if let (Some(code_points), Some(other)) = (self.code_points.as_mut(), other.code_points) {
+            for (c, count) in other {
+                *code_points.entry(c).or_default() += count;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_char_class() {
+        for (c, class) in [
+            ('a', CharClass::Letter),
+            ('語', CharClass::Letter),
+            ('\u{301}', CharClass::Mark),
+            ('7', CharClass::Digit),
+            ('½', CharClass::Digit),
+            ('“', CharClass::Punctuation),
+            ('-', CharClass::Punctuation),
+            ('+', CharClass::Symbol),
+            ('€', CharClass::Symbol),
+            ('\u{FFFD}', CharClass::Symbol),
+            ('😀', CharClass::Emoji),
+            ('\u{200D}', CharClass::Emoji),
+            ('\u{1F1E9}', CharClass::Emoji),
+            ('\t', CharClass::Whitespace),
+            ('\u{3000}', CharClass::Whitespace),
+            ('\u{1B}', CharClass::Control),
+            ('\u{FEFF}', CharClass::Control),
+            ('\u{E000}', CharClass::Other),
+        ] {
+            assert_eq!(CharClass::of(c), class, "{c:?}");
+        }
+        // The fast path agrees with the general categories
+        for c in '\0'..='\x7F' {
+            let expected = match c.general_category_group() {
+                _ if c.is_whitespace() => CharClass::Whitespace,
+                GeneralCategoryGroup::Letter => CharClass::Letter,
+                GeneralCategoryGroup::Number => CharClass::Digit,
+                GeneralCategoryGroup::Punctuation => CharClass::Punctuation,
+                GeneralCategoryGroup::Symbol => CharClass::Symbol,
+                _ => CharClass::Control,
+            };
+            assert_eq!(CharClass::of(c), expected, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn test_char_classes() {
+        let mut classes = CharClasses::default().with_code_points();
+        classes.update(&Chunk::from_text("“Hi,” 👋🏽\u{7}\n"));
+        let mut total = CharClasses::default().with_code_points();
+        total += classes;
+        total += CharClasses::default().with_code_points();
+        total.update(&Chunk::from_text("hi"));
+
+        let counts: Vec<usize> = CharClass::ALL
+            .iter()
+            .map(|&class| total.get(class))
+            .collect();
+        assert_eq!(counts, [4, 0, 0, 3, 0, 2, 2, 1, 0]);
+        let code_points = total.code_points().unwrap();
+        assert_eq!(code_points[..2], [('i', 2), ('\u{7}', 1)]);
+        assert_eq!(code_points.len(), 11);
+        assert_eq!(CharClasses::default().code_points(), None);
+    }
+}
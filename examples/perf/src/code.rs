@@ -0,0 +1,706 @@
+use std::path::Path;
+
+use crate::metric::{Chunk, Metric};
+use crate::resume::{Resumable, Resume};
+
+/// A kind of string literal
+#[derive(Debug)]
+struct Quote {
+    open: &'static str,
+    close: &'static str,
+    /// A backslash escapes the next character
+    escapes: bool,
+    /// The string can go on for more than one line
+    multiline: bool,
+}
+
+const fn quote(delimiter: &'static str, escapes: bool, multiline: bool) -> Quote {
+    Quote {
+        open: delimiter,
+        close: delimiter,
+        escapes,
+        multiline,
+    }
+}
+
+const DOUBLE: Quote = quote("\"", true, false);
+const SINGLE: Quote = quote("'", true, false);
+
+/// Comment and string syntax of a language, as far as it matters to tell
+/// code from comments
+#[derive(Debug)]
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// Block comments can be nested, like in Rust
+    nested: bool,
+    /// Longer delimiters come first, e.g. `"""` before `"`
+    quotes: &'static [Quote],
+    /// Line comments only start words, like `#` in shell scripts, where
+    /// `$#` isn't a comment
+    comments_start_words: bool,
+    /// Rust's raw strings like `r#"..."#`, and character literals like
+    /// `'"'`, which aren't to be confused with lifetimes like `'a`
+    rust_literals: bool,
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    nested: false,
+    quotes: &[DOUBLE, SINGLE],
+    comments_start_words: false,
+    rust_literals: false,
+};
+
+const HASH: Syntax = Syntax {
+    line_comments: &["#"],
+    block_comments: &[],
+    nested: false,
+    quotes: &[DOUBLE, SINGLE],
+    comments_start_words: false,
+    rust_literals: false,
+};
+
+const MARKUP: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: &[("<!--", "-->")],
+    nested: false,
+    // Quotes in text are apostrophes more often than not
+    quotes: &[],
+    comments_start_words: false,
+    rust_literals: false,
+};
+
+const CSS: Syntax = Syntax {
+    line_comments: &[],
+    ..C_LIKE
+};
+
+const GO: Syntax = Syntax {
+    quotes: &[DOUBLE, SINGLE, quote("`", false, true)],
+    ..C_LIKE
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    quotes: &[DOUBLE, SINGLE, quote("`", true, true)],
+    ..C_LIKE
+};
+
+const RUST: Syntax = Syntax {
+    nested: true,
+    quotes: &[quote("\"", true, true)],
+    rust_literals: true,
+    ..C_LIKE
+};
+
+const HASKELL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("{-", "-}")],
+    nested: true,
+    // Single quotes are also part of names like `x'`
+    quotes: &[DOUBLE],
+    ..C_LIKE
+};
+
+const LUA: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("--[[", "]]")],
+    ..C_LIKE
+};
+
+const SQL: Syntax = Syntax {
+    line_comments: &["--"],
+    quotes: &[quote("'", false, true)],
+    ..C_LIKE
+};
+
+const PYTHON: Syntax = Syntax {
+    quotes: &[
+        quote("\"\"\"", true, true),
+        quote("'''", true, true),
+        DOUBLE,
+        SINGLE,
+    ],
+    ..HASH
+};
+
+const RUBY: Syntax = Syntax {
+    block_comments: &[("=begin", "=end")],
+    ..HASH
+};
+
+const SHELL: Syntax = Syntax {
+    quotes: &[quote("\"", true, true), quote("'", false, true)],
+    comments_start_words: true,
+    ..HASH
+};
+
+const TOML: Syntax = Syntax {
+    quotes: &[
+        quote("\"\"\"", true, true),
+        quote("'''", false, true),
+        DOUBLE,
+        quote("'", false, false),
+    ],
+    ..HASH
+};
+
+/// Plain values are often prose, so only comments count
+const YAML: Syntax = Syntax {
+    quotes: &[],
+    comments_start_words: true,
+    ..HASH
+};
+
+/// A programming or markup language, told apart by file extension
+///
+/// Sorted by name, which is the order languages are reported in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Language {
+    C,
+    CSharp,
+    Cpp,
+    Css,
+    Go,
+    Haskell,
+    Html,
+    Java,
+    JavaScript,
+    Lua,
+    Python,
+    Ruby,
+    Rust,
+    Shell,
+    Sql,
+    Toml,
+    TypeScript,
+    Xml,
+    Yaml,
+}
+
+impl Language {
+    /// The language of a file with the given extension, like `rs`, if
+    /// it's a known one.
+    /// Header files ending in `.h` are taken to be C.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "c" | "h" => Self::C,
+            "cs" => Self::CSharp,
+            "cc" | "cpp" | "cxx" | "c++" | "hh" | "hpp" | "hxx" => Self::Cpp,
+            "css" => Self::Css,
+            "go" => Self::Go,
+            "hs" => Self::Haskell,
+            "html" | "htm" => Self::Html,
+            "java" => Self::Java,
+            "js" | "mjs" | "cjs" | "jsx" => Self::JavaScript,
+            "lua" => Self::Lua,
+            "py" | "pyi" | "pyw" => Self::Python,
+            "rb" => Self::Ruby,
+            "rs" => Self::Rust,
+            "sh" | "bash" | "zsh" | "ksh" => Self::Shell,
+            "sql" => Self::Sql,
+            "toml" => Self::Toml,
+            "ts" | "mts" | "cts" | "tsx" => Self::TypeScript,
+            "xml" | "svg" => Self::Xml,
+            "yaml" | "yml" => Self::Yaml,
+            _ => return None,
+        })
+    }
+
+    /// The language of the file at `path`, going by its extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        Self::from_extension(path.as_ref().extension()?.to_str()?)
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::C => "C",
+            Self::CSharp => "C#",
+            Self::Cpp => "C++",
+            Self::Css => "CSS",
+            Self::Go => "Go",
+            Self::Haskell => "Haskell",
+            Self::Html => "HTML",
+            Self::Java => "Java",
+            Self::JavaScript => "JavaScript",
+            Self::Lua => "Lua",
+            Self::Python => "Python",
+            Self::Ruby => "Ruby",
+            Self::Rust => "Rust",
+            Self::Shell => "Shell",
+            Self::Sql => "SQL",
+            Self::Toml => "TOML",
+            Self::TypeScript => "TypeScript",
+            Self::Xml => "XML",
+            Self::Yaml => "YAML",
+        }
+    }
+
+    const fn syntax(self) -> &'static Syntax {
+        match self {
+            Self::C | Self::CSharp | Self::Cpp | Self::Java => &C_LIKE,
+            Self::Css => &CSS,
+            Self::Go => &GO,
+            Self::JavaScript | Self::TypeScript => &JAVASCRIPT,
+            Self::Rust => &RUST,
+            Self::Haskell => &HASKELL,
+            Self::Lua => &LUA,
+            Self::Sql => &SQL,
+            Self::Python => &PYTHON,
+            Self::Ruby => &RUBY,
+            Self::Shell => &SHELL,
+            Self::Toml => &TOML,
+            Self::Yaml => &YAML,
+            Self::Html | Self::Xml => &MARKUP,
+        }
+    }
+}
+
+impl std::fmt::Display for Language {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Number of lines of code, comments, and blank lines
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLines {
+    /// Lines with any code on them, even if they also have a comment
+    pub code: usize,
+    /// Lines with nothing but comments
+    pub comments: usize,
+    /// Lines with nothing but whitespace, even inside comments or strings
+    pub blanks: usize,
+}
+
+impl SourceLines {
+    pub const fn total(&self) -> usize {
+        self.code + self.comments + self.blanks
+    }
+
+    /// Lines counted since `earlier`, a count of the same text so far
+    const fn since(self, earlier: Self) -> Self {
+        Self {
+            code: self.code - earlier.code,
+            comments: self.comments - earlier.comments,
+            blanks: self.blanks - earlier.blanks,
+        }
+    }
+}
+
+/// Add up the lines of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for SourceLines {
+    fn add_assign(&mut self, other: Self) {
+        self.code += other.code;
+        self.comments += other.comments;
+        self.blanks += other.blanks;
+    }
+}
+
+impl std::ops::Add for SourceLines {
+    type Output = Self;
+
+    fn add(mut self, other: Self) -> Self {
+        self += other;
+        self
+    }
+}
+
+/// Where the lexer is at the start of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Code,
+    /// Inside the block comment with the given delimiters, nested `depth`
+    /// deep
+    Comment {
+        block: usize,
+        depth: usize,
+    },
+    String {
+        quote: usize,
+    },
+    /// Inside a Rust raw string, which ends with `"` and this many `#`
+    RawString {
+        hashes: usize,
+    },
+}
+
+/// Tells code from comments line by line
+#[derive(Debug, Clone)]
+struct Lexer {
+    syntax: &'static Syntax,
+    state: State,
+    /// The current line so far, as tokens might be split across chunks
+    line: String,
+    lines: SourceLines,
+}
+
+impl Lexer {
+    fn new(language: Language) -> Self {
+        Self {
+            syntax: language.syntax(),
+            state: State::Code,
+            line: String::new(),
+            lines: SourceLines::default(),
+        }
+    }
+}
+
+impl Resume for Lexer {
+    fn update(&mut self, text: &str) {
+        let mut rest = text;
+        while let Some(end) = rest.find('\n') {
+            let mut line = std::mem::take(&mut self.line);
+            line.push_str(&rest[..end]);
+            self.add(&line);
+            line.clear();
+            self.line = line;
+            rest = &rest[end + 1..];
+        }
+        self.line.push_str(rest);
+    }
+
+    /// Like [`Counts::lines`](crate::Counts::lines), a last line without
+    /// a line break counts as long as it isn't empty
+    fn finish(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.add(&line);
+        }
+    }
+
+    fn resume(&mut self, next: Self, end: &Self) {
+        self.lines += next.lines.since(end.lines);
+        self.state = next.state;
+        self.line = next.line;
+    }
+}
+
+impl Lexer {
+    /// Count a line, without its line break
+    fn add(&mut self, line: &str) {
+        let (code, comment) = self.lex(line);
+        if code {
+            self.lines.code += 1;
+        } else if comment {
+            self.lines.comments += 1;
+        } else {
+            self.lines.blanks += 1;
+        }
+    }
+
+    /// Whether a line has code and comments on it.
+    /// All delimiters are ASCII, so the line is scanned byte by byte.
+    fn lex(&mut self, line: &str) -> (bool, bool) {
+        let syntax = self.syntax;
+        let bytes = line.as_bytes();
+        let (mut code, mut comment) = (false, false);
+        let mut i = 0;
+        while i < bytes.len() {
+            let rest = &bytes[i..];
+            match self.state {
+                State::Code if rest[0].is_ascii_whitespace() => i += 1,
+                State::Code => {
+                    if let Some(block) = syntax
+                        .block_comments
+                        .iter()
+                        .position(|(open, _)| rest.starts_with(open.as_bytes()))
+                    {
+                        self.state = State::Comment { block, depth: 1 };
+                        comment = true;
+                        i += syntax.block_comments[block].0.len();
+                        continue;
+                    }
+                    let starts_word = i == 0 || bytes[i - 1].is_ascii_whitespace();
+                    if syntax
+                        .line_comments
+                        .iter()
+                        .any(|start| rest.starts_with(start.as_bytes()))
+                        && (starts_word || !syntax.comments_start_words)
+                    {
+                        comment = true;
+                        break;
+                    }
+                    code = true;
+                    if let Some(len) = self.rust_literal(line, i) {
+                        i += len;
+                    } else if let Some(quote) = syntax
+                        .quotes
+                        .iter()
+                        .position(|quote| rest.starts_with(quote.open.as_bytes()))
+                    {
+                        self.state = State::String { quote };
+                        i += syntax.quotes[quote].open.len();
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::Comment { block, depth } => {
+                    let (open, close) = syntax.block_comments[block];
+                    if rest.starts_with(close.as_bytes()) {
+                        self.state = if depth == 1 {
+                            State::Code
+                        } else {
+                            State::Comment {
+                                block,
+                                depth: depth - 1,
+                            }
+                        };
+                        comment = true;
+                        i += close.len();
+                    } else if syntax.nested && rest.starts_with(open.as_bytes()) {
+                        self.state = State::Comment {
+                            block,
+                            depth: depth + 1,
+                        };
+                        comment = true;
+                        i += open.len();
+                    } else {
+                        comment |= !rest[0].is_ascii_whitespace();
+                        i += 1;
+                    }
+                }
+                State::String { quote } => {
+                    let quote = &syntax.quotes[quote];
+                    code = true;
+                    if quote.escapes && rest[0] == b'\\' {
+                        i += 2;
+                    } else if rest.starts_with(quote.close.as_bytes()) {
+                        self.state = State::Code;
+                        i += quote.close.len();
+                    } else {
+                        i += 1;
+                    }
+                }
+                State::RawString { hashes } => {
+                    code = true;
+                    if rest[0] == b'"'
+                        && rest.len() > hashes
+                        && rest[1..=hashes].iter().all(|&b| b == b'#')
+                    {
+                        self.state = State::Code;
+                        i += 1 + hashes;
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+        }
+        // Strings that can't span lines end with them, even if they aren't
+        // closed
+        if let State::String { quote } = self.state {
+            if !syntax.quotes[quote].multiline {
+                self.state = State::Code;
+            }
+        }
+        (code, comment)
+    }
+
+    /// Length of a Rust character literal or lifetime at `i`, or of the
+    /// start of a raw string, if there's one
+    fn rust_literal(&mut self, line: &str, i: usize) -> Option<usize> {
+        if !self.syntax.rust_literals {
+            return None;
+        }
+        let bytes = line.as_bytes();
+        let rest = &bytes[i..];
+        if rest[0] == b'\'' {
+            // `'\''` or `'\u{1F600}'`
+            if rest.get(1) == Some(&b'\\') {
+                let end = rest.iter().skip(3).position(|&b| b == b'\'');
+                return Some(end.map_or(1, |end| end + 4));
+            }
+            // `'"'`, or a lifetime like `'a` otherwise
+            let len = line[i + 1..].chars().next().map_or(0, char::len_utf8);
+            return Some(if rest.get(len + 1) == Some(&b'\'') {
+                len + 2
+            } else {
+                1
+            });
+        }
+        // `r"..."`, `r#"..."#`, or `br"..."`, but not the end of a name
+        // like `for"`
+        let prefix = if rest.starts_with(b"br") { 2 } else { 1 };
+        let starts_name = i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_');
+        if rest[prefix - 1] != b'r' || !starts_name {
+            return None;
+        }
+        let hashes = rest[prefix..].iter().take_while(|&&b| b == b'#').count();
+        if rest.get(prefix + hashes) != Some(&b'"') {
+            return None;
+        }
+        self.state = State::RawString { hashes };
+        Some(prefix + hashes + 1)
+    }
+}
+
+/// Lines of code, comments, and blank lines of source code in a given
+/// [`Language`]
+///
+/// A line with code and a comment on it counts as code. String literals
+/// are code, even if they look like comments, and so are docstrings.
+/// Like most such tools, it only knows enough of each language to tell
+/// comments from code, so some rare constructs like Ruby's heredocs or
+/// nested string interpolation can throw it off.
+///
+/// Pieces of the input counted on their own assume they start outside of
+/// comments and strings, and keep their start to count again once the
+/// text before is known.
+#[derive(Debug, Clone)]
+pub struct CodeLines {
+    language: Language,
+    lexer: Resumable<Lexer>,
+}
+
+impl CodeLines {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            lexer: Resumable::new(Lexer::new(language)),
+        }
+    }
+
+    pub const fn language(&self) -> Language {
+        self.language
+    }
+
+    pub const fn lines(&self) -> SourceLines {
+        self.lexer.get().lines
+    }
+}
+
+impl Metric for CodeLines {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        self.lexer.update(text);
+    }
+
+    fn finish(&mut self) {
+        self.lexer.finish();
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.lexer.merge(next.lexer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resume::MAX_HEAD_LEN;
+
+    fn count(pieces: &[&str], language: Language) -> SourceLines {
+        let mut merged = CodeLines::new(language);
+        for piece in pieces {
+            let mut lines = CodeLines::new(language);
+            lines.update(&Chunk::from_text(piece));
+            merged.merge(lines);
+        }
+        merged.finish();
+        merged.lines()
+    }
+
+    fn lines(code: usize, comments: usize, blanks: usize) -> SourceLines {
+        SourceLines {
+            code,
+            comments,
+            blanks,
+        }
+    }
+
+    #[test]
+    fn test_language() {
+        assert_eq!(Language::from_path("src/main.rs"), Some(Language::Rust));
+        assert_eq!(Language::from_path("setup.PY"), Some(Language::Python));
+        assert_eq!(Language::from_path("a/b.tar.gz"), None);
+        assert_eq!(Language::from_path("Makefile"), None);
+        assert_eq!(Language::Cpp.to_string(), "C++");
+    }
+
+    #[test]
+    fn test_rust() {
+        let text = "/* outer /* inner */ still a comment */\n\
+                    fn main() { // trailing comment\n\
+                    \x20   let s = \"// not a comment /*\";\n\
+                    \n\
+                    \x20   let r = r#\"a \"quoted\" /* string\n\
+                    */\"#;\n\
+                    \x20   let c = '\"'; // a char, not a string\n\
+                    \x20   fn f<'a>(x: &'a str) {}\n\
+                    \x20   /// Doc comment\n\
+                    }";
+        assert_eq!(count(&[text], Language::Rust), lines(7, 2, 1));
+    }
+
+    #[test]
+    fn test_c() {
+        let text = "/*\n * Block\n *\n */ int x; /* code before */\n\
+                    char *s = \"/* not a comment\";\n'\"'; // quote\n";
+        assert_eq!(count(&[text], Language::C), lines(3, 3, 0));
+        // C block comments don't nest
+        assert_eq!(count(&["/* /* */ x\n"], Language::C), lines(1, 0, 0));
+    }
+
+    #[test]
+    fn test_python() {
+        let text = "# comment\nx = \"# not a comment\"\n\"\"\"Docstring\n\n# still\n\"\"\"\n  \n";
+        assert_eq!(count(&[text], Language::Python), lines(4, 1, 2));
+    }
+
+    #[test]
+    fn test_shell() {
+        let text = "#!/bin/sh\necho $# ${#x} # count\necho 'it''s' \"#\"\n";
+        assert_eq!(count(&[text], Language::Shell), lines(2, 1, 0));
+    }
+
+    #[test]
+    fn test_total() {
+        let mut total = count(&["a\n\n// b"], Language::Rust);
+        total += count(&["# c\n"], Language::Python);
+        assert_eq!(total, lines(1, 2, 1));
+        assert_eq!(total.total(), 4);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "/* a /* b */\n*/ x // y\n\"/*\n\"r#\"*/\"#\n'\\'' é\n// z";
+        let expected = count(&[text], Language::Rust);
+        assert_eq!(expected, lines(4, 2, 0));
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first) {
+                let pieces = [&text[..first], &text[first..second], &text[second..]];
+                assert_eq!(
+                    count(&pieces, Language::Rust),
+                    expected,
+                    "split at {first} and {second}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_head() {
+        // The second piece starts inside a comment and is counted in two
+        // chunks, so only the first is counted again. The state after it
+        // is the same either way, so the rest still counts right.
+        let long = "x\n".repeat(MAX_HEAD_LEN);
+        let text = format!("/*\n{long}*/\n{long}// end\n");
+        let expected = count(&[&text], Language::C);
+        assert_eq!(expected, lines(MAX_HEAD_LEN, MAX_HEAD_LEN + 3, 0));
+
+        let (first, second) = text.split_at(text.len() / 4);
+        let mut merged = CodeLines::new(Language::C);
+        merged.update(&Chunk::from_text(first));
+        let mut next = CodeLines::new(Language::C);
+        next.update(&Chunk::from_text(&second[..MAX_HEAD_LEN + 1000]));
+        next.update(&Chunk::from_text(&second[MAX_HEAD_LEN + 1000..]));
+        merged.merge(next);
+        merged.finish();
+        assert_eq!(merged.lines(), expected);
+    }
+}
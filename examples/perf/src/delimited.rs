@@ -0,0 +1,543 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind, Read};
+use std::str::FromStr;
+
+use crate::metric::{Chunk, Metric};
+use crate::words::{WordBoundary, Words};
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// A column picked by its name in the header, or by its number,
+/// starting at 1 like `cut -f` does
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+/// Numbers are taken as indexes, anything else as a name
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<usize>() {
+            Ok(0) => Err("Columns are numbered from 1".to_string()),
+            Ok(index) => Ok(Self::Index(index)),
+            Err(_) if s.is_empty() => Err("Empty column name".to_string()),
+            Err(_) => Ok(Self::Name(s.to_string())),
+        }
+    }
+}
+
+/// Words and characters of the fields in a column
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ColumnCounts {
+    /// Name of the column in the header, if there is one
+    pub name: Option<String>,
+    pub words: usize,
+    pub chars: usize,
+}
+
+/// A record with a different number of fields than the first one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RaggedRecord {
+    /// Number of the record, counting the header, starting at 1
+    pub record: usize,
+    pub fields: usize,
+}
+
+/// Records of delimited data like CSV, without the header
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Records {
+    records: usize,
+    /// Fields of the first record, or `None` if it differs between inputs
+    fields: Option<usize>,
+    ragged: Vec<RaggedRecord>,
+    ragged_count: usize,
+    /// Counts of the selected columns, by index starting at 0
+    columns: BTreeMap<usize, ColumnCounts>,
+}
+
+impl Records {
+    /// Number of records, without the header
+    pub const fn len(&self) -> usize {
+        self.records
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.records == 0
+    }
+
+    /// Number of fields of the first record, which the others should have
+    /// too
+    pub const fn fields(&self) -> Option<usize> {
+        self.fields
+    }
+
+    /// Records with a different number of fields than the first
+    pub fn ragged(&self) -> &[RaggedRecord] {
+        &self.ragged
+    }
+
+    pub const fn ragged_count(&self) -> usize {
+        self.ragged_count
+    }
+
+    /// Counts of the selected columns, by their number starting at 1
+    pub fn columns(&self) -> impl Iterator<Item = (usize, &ColumnCounts)> {
+        self.columns
+            .iter()
+            .map(|(&index, counts)| (index + 1, counts))
+    }
+}
+
+/// Add up the records of separate inputs, e.g. for a total over several
+/// files. Columns are added up by their position, and the ragged records
+/// themselves are only kept for each input.
+impl std::ops::AddAssign for Records {
+    fn add_assign(&mut self, other: Self) {
+        self.fields = match (self.records, other.records) {
+            (_, 0) => self.fields,
+            (0, _) => other.fields,
+            _ => self.fields.filter(|&fields| other.fields == Some(fields)),
+        };
+        self.records += other.records;
+        self.ragged_count += other.ragged_count;
+        for (index, other) in other.columns {
+            let counts = self.columns.entry(index).or_default();
+            counts.name = counts.name.take().or(other.name);
+            counts.words += other.words;
+            counts.chars += other.chars;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// At the start of a field
+    Start,
+    Unquoted,
+    Quoted,
+    /// After a quote in a quoted field, which either ends it or is the
+    /// first of two quotes that stand for one
+    Quote,
+}
+
+/// Splits delimited data into records and fields
+#[derive(Debug)]
+struct Parser {
+    delimiter: u8,
+    boundary: WordBoundary,
+    /// The first record is a header with the names of the columns
+    header: bool,
+    /// Columns to count, before they are looked up in the header
+    selection: Option<Vec<Column>>,
+    /// Indexes of the columns to count, starting at 0, or `None` for all
+    selected: Option<Vec<usize>>,
+    state: State,
+    /// A carriage return that may be part of a line break
+    carriage_return: bool,
+    field: Vec<u8>,
+    /// Fields of the current record so far
+    fields: usize,
+    /// Whether the current record has any fields yet, so that blank lines
+    /// are skipped
+    in_record: bool,
+    /// Whether a field of the current record was written to the text
+    written: bool,
+    /// Records so far, counting the header
+    read: usize,
+    words: BTreeMap<usize, Words>,
+    records: Records,
+    /// A selected column that isn't in the header, or column number 0
+    missing: Option<Column>,
+}
+
+impl Parser {
+    fn new(delimiter: u8, boundary: WordBoundary) -> Self {
+        Self {
+            delimiter,
+            boundary,
+            header: false,
+            selection: None,
+            selected: None,
+            state: State::Start,
+            carriage_return: false,
+            field: Vec::new(),
+            fields: 0,
+            in_record: false,
+            written: false,
+            read: 0,
+            words: BTreeMap::new(),
+            records: Records::default(),
+            missing: None,
+        }
+    }
+
+    /// Look up the selected columns, by name in the header if needed
+    fn select(&mut self, names: &[String]) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+        let mut selected = Vec::new();
+        for column in selection {
+            match column {
+                Column::Index(index) => match index.checked_sub(1) {
+                    Some(index) => selected.push(index),
+                    None => self.missing = Some(column),
+                },
+                Column::Name(ref name) => match names.iter().position(|other| other == name) {
+                    Some(index) => selected.push(index),
+                    None => self.missing = Some(column),
+                },
+            }
+        }
+        self.selected = Some(selected);
+    }
+
+    fn is_selected(&self, index: usize) -> bool {
+        self.selected
+            .as_ref()
+            .is_none_or(|selected| selected.contains(&index))
+    }
+
+    fn step(&mut self, byte: u8, text: &mut Vec<u8>) {
+        if std::mem::take(&mut self.carriage_return) {
+            if byte == b'\n' {
+                self.end_record(text);
+                return;
+            }
+            self.in_record = true;
+            self.field.push(b'\r');
+            if self.state == State::Start {
+                self.state = State::Unquoted;
+            }
+        }
+        match (self.state, byte) {
+            (State::Start | State::Unquoted | State::Quote, b'\r') => self.carriage_return = true,
+            (State::Start | State::Unquoted | State::Quote, b'\n') => self.end_record(text),
+            (State::Start | State::Unquoted | State::Quote, _) if byte == self.delimiter => {
+                self.end_field(text);
+            }
+            (State::Start, b'"') => {
+                self.in_record = true;
+                self.state = State::Quoted;
+            }
+            (State::Quoted, b'"') => self.state = State::Quote,
+            (State::Quote, b'"') => {
+                self.field.push(b'"');
+                self.state = State::Quoted;
+            }
+            (State::Quoted, _) => self.field.push(byte),
+            // Text after the closing quote isn't allowed, but kept
+            (State::Start | State::Unquoted | State::Quote, _) => {
+                self.in_record = true;
+                self.field.push(byte);
+                self.state = State::Unquoted;
+            }
+        }
+    }
+
+    fn end_field(&mut self, text: &mut Vec<u8>) {
+        self.in_record = true;
+        self.state = State::Start;
+        let index = self.fields;
+        self.fields += 1;
+        let field = std::mem::take(&mut self.field);
+        if self.header && self.read == 0 {
+            self.records.columns.entry(index).or_default().name =
+                Some(String::from_utf8_lossy(&field).into_owned());
+            return;
+        }
+        if !self.is_selected(index) {
+            return;
+        }
+        let counts = self.records.columns.entry(index).or_default();
+        counts.chars += field.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+        let words = self
+            .words
+            .entry(index)
+            .or_insert_with(|| Words::new(self.boundary));
+        words.update(&Chunk::from_text(&String::from_utf8_lossy(&field)));
+        // Fields of a column are counted as if on lines of their own
+        words.update(&Chunk::from_text("\n"));
+
+        if std::mem::replace(&mut self.written, true) {
+            text.push(b'\t');
+        }
+        // Each record is written on a line of its own
+        text.extend(field.iter().map(|&b| match b {
+            b'\r' | b'\n' => b' ',
+            _ => b,
+        }));
+    }
+
+    fn end_record(&mut self, text: &mut Vec<u8>) {
+        if !self.in_record {
+            // Blank lines aren't records
+            self.state = State::Start;
+            return;
+        }
+        self.end_field(text);
+        let fields = std::mem::take(&mut self.fields);
+        self.in_record = false;
+        self.written = false;
+        self.read += 1;
+        if self.read == 1 {
+            self.records.fields = Some(fields);
+            if self.header {
+                let names: Vec<String> = self
+                    .records
+                    .columns
+                    .values()
+                    .filter_map(|counts| counts.name.clone())
+                    .collect();
+                self.select(&names);
+                // Only the selected columns are counted
+                let selected = self.selected.clone();
+                self.records.columns.retain(|index, _| {
+                    selected
+                        .as_ref()
+                        .is_none_or(|selected| selected.contains(index))
+                });
+                return;
+            }
+        } else if self.records.fields != Some(fields) {
+            self.records.ragged.push(RaggedRecord {
+                record: self.read,
+                fields,
+            });
+            self.records.ragged_count += 1;
+        }
+        self.records.records += 1;
+        text.push(b'\n');
+    }
+
+    fn finish(&mut self, text: &mut Vec<u8>) {
+        if self.carriage_return {
+            self.step(b'\n', text);
+        } else if self.in_record || !self.field.is_empty() {
+            self.end_record(text);
+        }
+        for (index, words) in &mut self.words {
+            words.finish();
+            self.records.columns.entry(*index).or_default().words = words.count();
+        }
+    }
+}
+
+/// The fields of delimited data like CSV or TSV, read from the data as
+/// it's read
+///
+/// Follows RFC 4180: fields in double quotes can contain the delimiter,
+/// line breaks, and quotes doubled to `""`, and records end with CRLF or
+/// LF. Blank lines are skipped. Each record is written on a line of its
+/// own, with the selected fields separated by tabs and the line breaks
+/// within fields replaced with spaces, so lines count records. The
+/// records, the fields of each, and the words and characters of each
+/// selected column are counted along the way.
+///
+/// The data is expected in UTF-8 or another encoding compatible with
+/// ASCII.
+#[derive(Debug)]
+pub struct DelimitedText<R> {
+    input: R,
+    parser: Parser,
+    buffer: Vec<u8>,
+    /// Text from the data read so far, of which `start..` is left
+    text: Vec<u8>,
+    start: usize,
+    eof: bool,
+}
+
+impl<R: Read> DelimitedText<R> {
+    pub fn new(input: R, delimiter: u8, boundary: WordBoundary) -> Self {
+        Self {
+            input,
+            parser: Parser::new(delimiter, boundary),
+            buffer: vec![0; BUF_SIZE],
+            text: Vec::new(),
+            start: 0,
+            eof: false,
+        }
+    }
+
+    /// Take the first record as a header with the names of the columns,
+    /// instead of data
+    #[must_use]
+    pub const fn with_header(mut self) -> Self {
+        self.parser.header = true;
+        self
+    }
+
+    /// Only count these columns. Picking them by name needs a header.
+    #[must_use]
+    pub fn with_columns(mut self, columns: Vec<Column>) -> Self {
+        if columns
+            .iter()
+            .any(|column| matches!(column, Column::Name(_)))
+        {
+            self.parser.header = true;
+        }
+        self.parser.selection = Some(columns);
+        if !self.parser.header {
+            self.parser.select(&[]);
+        }
+        self
+    }
+
+    /// Records read so far
+    pub const fn records(&self) -> &Records {
+        &self.parser.records
+    }
+
+    pub fn into_records(self) -> Records {
+        self.parser.records
+    }
+}
+
+impl<R: Read> Read for DelimitedText<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.start == self.text.len() {
+            if let Some(column) = self.parser.missing.take() {
+                let message = match column {
+                    Column::Index(_) => "Columns are numbered from 1".to_string(),
+                    Column::Name(name) => format!("No column named {name}"),
+                };
+                return Err(io::Error::new(ErrorKind::InvalidInput, message));
+            }
+            if self.eof {
+                return Ok(0);
+            }
+            self.text.clear();
+            self.start = 0;
+            let bytes_read = match self.input.read(&mut self.buffer) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if bytes_read == 0 {
+                self.parser.finish(&mut self.text);
+                self.eof = true;
+            }
+            for &byte in &self.buffer[..bytes_read] {
+                self.parser.step(byte, &mut self.text);
+            }
+        }
+        let len = buf.len().min(self.text.len() - self.start);
+        buf[..len].copy_from_slice(&self.text[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Trickle;
+
+    fn extract(
+        data: &str,
+        delimiter: u8,
+        columns: Option<Vec<Column>>,
+        header: bool,
+    ) -> io::Result<(String, Records)> {
+        let open = |input| {
+            let reader = DelimitedText::new(input, delimiter, WordBoundary::Ascii);
+            let reader = if header { reader.with_header() } else { reader };
+            match columns.clone() {
+                Some(columns) => reader.with_columns(columns),
+                None => reader,
+            }
+        };
+        let mut reader = open(Box::new(data.as_bytes()) as Box<dyn Read>);
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut trickled = open(Box::new(Trickle(data.as_bytes())) as Box<dyn Read>);
+        let mut same = String::new();
+        trickled.read_to_string(&mut same)?;
+        assert_eq!(same, text, "{data:?} byte by byte");
+        assert_eq!(trickled.records(), reader.records());
+        Ok((text, reader.into_records()))
+    }
+
+    fn columns(records: &Records) -> Vec<(usize, Option<&str>, usize, usize)> {
+        records
+            .columns()
+            .map(|(index, counts)| (index, counts.name.as_deref(), counts.words, counts.chars))
+            .collect()
+    }
+
+    #[test]
+    fn test_csv() {
+        let data = "id,text,note\r\n1,\"Hello, \"\"quoted\"\"\r\nworld\",x\r\n\r\n\
+                    2,plain words here,\n3,\"\",y,extra\n4,é";
+        let (text, records) = extract(data, b',', None, true).unwrap();
+        assert_eq!(
+            text,
+            "1\tHello, \"quoted\"  world\tx\n2\tplain words here\t\n3\t\ty\textra\n4\té\n"
+        );
+        assert_eq!(records.len(), 4);
+        assert_eq!(records.fields(), Some(3));
+        assert_eq!(
+            records.ragged(),
+            [
+                RaggedRecord {
+                    record: 4,
+                    fields: 4
+                },
+                RaggedRecord {
+                    record: 5,
+                    fields: 2
+                },
+            ]
+        );
+        assert_eq!(
+            columns(&records),
+            [
+                (1, Some("id"), 4, 4),
+                (2, Some("text"), 7, 39),
+                (3, Some("note"), 2, 2),
+                (4, None, 1, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_selection() {
+        let data = "a\tb\tc\nx y\tz\t\"q\"\n";
+        let select = |columns: &str| {
+            let columns = columns.split(',').map(|c| c.parse().unwrap()).collect();
+            extract(data, b'\t', Some(columns), false)
+        };
+        let (text, records) = select("3,1").unwrap();
+        assert_eq!(text, "a\tc\nx y\tq\n");
+        assert_eq!(records.len(), 2);
+        assert_eq!(columns(&records), [(1, None, 3, 4), (3, None, 2, 2)]);
+
+        let (text, records) = select("b").unwrap();
+        assert_eq!(text, "z\n");
+        assert_eq!(columns(&records), [(2, Some("b"), 1, 1)]);
+
+        assert_eq!(select("d").unwrap_err().to_string(), "No column named d");
+        assert!("0".parse::<Column>().is_err());
+        let error = extract(data, b'\t', Some(vec![Column::Index(0)]), false).unwrap_err();
+        assert_eq!(error.to_string(), "Columns are numbered from 1");
+    }
+
+    #[test]
+    fn test_totals() {
+        let (_, mut total) = extract("a,b\n1,2\n", b',', None, false).unwrap();
+        let (_, other) = extract("a,b\n1,2,3\n", b',', None, false).unwrap();
+        total += other;
+        assert_eq!(total.len(), 4);
+        assert_eq!(total.fields(), Some(2));
+        assert_eq!(total.ragged_count(), 1);
+        assert!(total.ragged().is_empty());
+        assert_eq!(
+            columns(&total),
+            [(1, None, 4, 4), (2, None, 4, 4), (3, None, 1, 1)]
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// Text encodings the input can be decoded from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    /// ISO-8859-1, where every byte is the code point of the same value
+    Latin1,
+    /// Latin-1 with printable characters like curly quotes and the euro
+    /// sign in place of most C1 control codes
+    Windows1252,
+}
+
+/// Longest byte order mark of any supported encoding
+pub(crate) const MAX_BOM_LEN: usize = 3;
+
+impl Encoding {
+    /// The byte order mark that may start text in this encoding
+    const fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => b"\xEF\xBB\xBF",
+            Self::Utf16Le => b"\xFF\xFE",
+            Self::Utf16Be => b"\xFE\xFF",
+            // Single-byte encodings have no byte order
+            Self::Latin1 | Self::Windows1252 => b"",
+        }
+    }
+
+    /// Pick the encoding for input starting with `bytes`, along with the
+    /// length of its byte order mark.
+    ///
+    /// An `explicit` encoding always wins, but its BOM is still skipped.
+    /// Otherwise, the BOM decides and input without one is UTF-8.
+    pub(crate) fn detect(bytes: &[u8], explicit: Option<Self>) -> (Self, usize) {
+        let encoding = explicit.unwrap_or_else(|| {
+            [Self::Utf8, Self::Utf16Le, Self::Utf16Be]
+                .into_iter()
+                .find(|encoding| bytes.starts_with(encoding.bom()))
+                .unwrap_or_default()
+        });
+        let bom = if bytes.starts_with(encoding.bom()) {
+            encoding.bom().len()
+        } else {
+            0
+        };
+        (encoding, bom)
+    }
+
+    /// Whether every ASCII character is encoded as the same single byte,
+    /// so lines and ASCII words can be counted without decoding
+    pub(crate) const fn is_ascii_compatible(self) -> bool {
+        !matches!(self, Self::Utf16Le | Self::Utf16Be)
+    }
+
+    /// Decode as much of `bytes` as possible and return how many bytes were
+    /// used.
+    ///
+    /// A sequence cut off at the end is left for the next call, unless this
+    /// is the end of the input (`eof`).
+    pub(crate) fn decode(
+        self,
+        bytes: &[u8],
+        eof: bool,
+        mut emit: impl FnMut(Decoded<'_>),
+    ) -> usize {
+        match self {
+            Self::Utf8 => decode_utf8(bytes, eof, &mut emit),
+            Self::Utf16Le => decode_utf16(bytes, u16::from_le_bytes, eof, &mut emit),
+            Self::Utf16Be => decode_utf16(bytes, u16::from_be_bytes, eof, &mut emit),
+            Self::Latin1 => decode_single_byte(bytes, char::from, &mut emit),
+            Self::Windows1252 => decode_single_byte(bytes, windows_1252, &mut emit),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Utf8 => "UTF-8",
+            Self::Utf16Le => "UTF-16LE",
+            Self::Utf16Be => "UTF-16BE",
+            Self::Latin1 => "ISO-8859-1",
+            Self::Windows1252 => "windows-1252",
+        })
+    }
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            "utf-16le" | "utf16le" => Ok(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(Self::Utf16Be),
+            "iso-8859-1" | "latin1" | "latin-1" => Ok(Self::Latin1),
+            "windows-1252" | "cp1252" => Ok(Self::Windows1252),
+            _ => Err(format!(
+                "Unknown encoding: {s} \
+                 (expected utf-8, utf-16le, utf-16be, iso-8859-1, or windows-1252)"
+            )),
+        }
+    }
+}
+
+/// A piece of decoded input
+pub(crate) enum Decoded<'a> {
+    Text(&'a str),
+    /// An invalid sequence at the given byte offset, which stands for
+    /// one U+FFFD replacement character
+    Invalid(usize),
+}
+
+/// Like `String::from_utf8_lossy`, but streaming
+fn decode_utf8(bytes: &[u8], eof: bool, emit: &mut impl FnMut(Decoded<'_>)) -> usize {
+    let mut offset = 0;
+    for chunk in bytes.utf8_chunks() {
+        if !chunk.valid().is_empty() {
+            emit(Decoded::Text(chunk.valid()));
+        }
+        offset += chunk.valid().len();
+
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        let incomplete = offset + invalid.len() == bytes.len()
+            && std::str::from_utf8(invalid).is_err_and(|e| e.error_len().is_none());
+        if incomplete && !eof {
+            break;
+        }
+        emit(Decoded::Invalid(offset));
+        offset += invalid.len();
+    }
+    offset
+}
+
+/// Decode UTF-16 code units read with `unit`, replacing unpaired surrogates
+fn decode_utf16(
+    bytes: &[u8],
+    unit: fn([u8; 2]) -> u16,
+    eof: bool,
+    emit: &mut impl FnMut(Decoded<'_>),
+) -> usize {
+    let unit_at = |offset: usize| unit([bytes[offset], bytes[offset + 1]]);
+    let mut text = String::with_capacity(bytes.len());
+    let mut offset = 0;
+
+    while offset + 2 <= bytes.len() {
+        let first = unit_at(offset);
+        let decoded = match first {
+            0xD800..=0xDBFF => {
+                let complete = offset + 4 <= bytes.len();
+                if !complete && !eof {
+                    // The low surrogate might be in the next chunk
+                    break;
+                }
+                let second = complete.then(|| unit_at(offset + 2));
+                second
+                    .and_then(|second| char::decode_utf16([first, second]).next())
+                    .and_then(Result::ok)
+                    .map(|c| (c, 4))
+            }
+            0xDC00..=0xDFFF => None,
+            _ => char::from_u32(u32::from(first)).map(|c| (c, 2)),
+        };
+
+        match decoded {
+            Some((c, len)) => {
+                text.push(c);
+                offset += len;
+            }
+            None => {
+                if !text.is_empty() {
+                    emit(Decoded::Text(&text));
+                    text.clear();
+                }
+                emit(Decoded::Invalid(offset));
+                offset += 2;
+            }
+        }
+    }
+
+    if !text.is_empty() {
+        emit(Decoded::Text(&text));
+    }
+    // A lone byte at the very end can't be a code unit
+    if eof && offset < bytes.len() {
+        emit(Decoded::Invalid(offset));
+        offset = bytes.len();
+    }
+    offset
+}
+
+/// Decode an encoding with one character per byte.
+/// Every byte is a valid character, and none is ever cut off.
+fn decode_single_byte(
+    bytes: &[u8],
+    decode: fn(u8) -> char,
+    emit: &mut impl FnMut(Decoded<'_>),
+) -> usize {
+    let text: String = bytes.iter().copied().map(decode).collect();
+    if !text.is_empty() {
+        emit(Decoded::Text(&text));
+    }
+    bytes.len()
+}
+
+/// Windows-1252 differs from Latin-1 only in 0x80..=0x9F.
+/// The five bytes it leaves undefined map to the C1 control codes,
+/// same as in the WHATWG Encoding Standard.
+fn windows_1252(byte: u8) -> char {
+    const HIGH: [char; 32] = [
+        '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8D}', 'Ž',
+        '\u{8F}', '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9D}',
+        'ž', 'Ÿ',
+    ];
+    match byte {
+        0x80..=0x9F => HIGH[usize::from(byte - 0x80)],
+        _ => char::from(byte),
+    }
+}
@@ -0,0 +1,319 @@
+use std::collections::HashMap;
+
+use crate::metric::{Chunk, Metric};
+use crate::segments::{Segmentation, Segments};
+use crate::WordBoundary;
+
+/// Summary of the words of a text
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Vocabulary {
+    /// Number of distinct words (types)
+    pub distinct: usize,
+    /// Number of words, counting every occurrence (tokens)
+    pub tokens: usize,
+    /// Words that occur exactly once
+    pub hapax_legomena: usize,
+    /// Average number of characters per word, over all occurrences
+    pub average_word_length: f64,
+    /// Number of characters of the longest word
+    pub max_word_length: usize,
+}
+
+impl Vocabulary {
+    /// Distinct words per word, from 0 to 1.
+    /// The more words repeat, the lower it gets.
+    pub fn type_token_ratio(&self) -> f64 {
+        if self.tokens == 0 {
+            0.0
+        } else {
+            self.distinct as f64 / self.tokens as f64
+        }
+    }
+}
+
+/// How often each word occurs
+///
+/// Words are told apart the same way [`Words`](crate::Words) counts them.
+/// Memory grows with the number of distinct words, not with the input.
+#[derive(Debug, Clone)]
+pub struct Frequencies {
+    boundary: WordBoundary,
+    /// Count upper- and lowercase spellings of a word as the same word
+    ignore_case: bool,
+    counts: HashMap<String, usize>,
+    /// Text before the first separator, which might continue a word from
+    /// the text before
+    head: String,
+    /// Whether there was a separator yet, so `tail` comes after `head`
+    split: bool,
+    /// Text after the last separator, which the next chunk might continue
+    tail: String,
+    /// UAX #29 segments whose boundaries aren't settled yet
+    segments: Segments,
+}
+
+impl Frequencies {
+    pub fn new(boundary: WordBoundary, ignore_case: bool) -> Self {
+        Self {
+            boundary,
+            ignore_case,
+            counts: HashMap::new(),
+            head: String::new(),
+            split: false,
+            tail: String::new(),
+            segments: Segments::new(Segmentation::Words).recording(),
+        }
+    }
+
+    /// How often `word` occurs
+    pub fn get(&self, word: &str) -> usize {
+        let word = if self.ignore_case {
+            word.to_lowercase()
+        } else {
+            word.to_string()
+        };
+        self.counts.get(&word).copied().unwrap_or(0)
+    }
+
+    /// Number of distinct words
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Statistics of all words counted so far.
+    /// Word lengths are measured in Unicode scalar values.
+    pub fn vocabulary(&self) -> Vocabulary {
+        let mut vocabulary = Vocabulary {
+            distinct: self.counts.len(),
+            ..Vocabulary::default()
+        };
+        let mut total_length = 0;
+        for (word, &count) in &self.counts {
+            let length = word.chars().count();
+            vocabulary.tokens += count;
+            vocabulary.max_word_length = vocabulary.max_word_length.max(length);
+            total_length += length * count;
+            if count == 1 {
+                vocabulary.hapax_legomena += 1;
+            }
+        }
+        if vocabulary.tokens > 0 {
+            vocabulary.average_word_length = total_length as f64 / vocabulary.tokens as f64;
+        }
+        vocabulary
+    }
+
+    /// The `n` most frequent words, most frequent first.
+    /// Words that occur equally often are sorted alphabetically.
+    pub fn top(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut top: Vec<_> = self
+            .counts
+            .iter()
+            .map(|(word, &count)| (word.as_str(), count))
+            .collect();
+        top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        top.truncate(n);
+        top
+    }
+
+    fn add(&mut self, word: &str) {
+        if word.is_empty() {
+            return;
+        }
+        if self.ignore_case {
+            *self.counts.entry(word.to_lowercase()).or_default() += 1;
+        } else if let Some(count) = self.counts.get_mut(word) {
+            *count += 1;
+        } else {
+            self.counts.insert(word.to_string(), 1);
+        }
+    }
+
+    /// Add the word made up of the tail and `rest`
+    fn end_word(&mut self, rest: &str) {
+        let mut word = std::mem::take(&mut self.tail);
+        word.push_str(rest);
+        self.add(&word);
+        word.clear();
+        self.tail = word;
+    }
+
+    fn take_segments(&mut self) {
+        for word in self.segments.take_words() {
+            self.add(&word);
+        }
+    }
+}
+
+impl Metric for Frequencies {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        let is_separator = match self.boundary {
+            WordBoundary::Ascii => |c: char| c.is_ascii_whitespace(),
+            WordBoundary::Unicode => char::is_whitespace,
+            WordBoundary::Uax29 => {
+                self.segments.update(text);
+                self.take_segments();
+                return;
+            }
+        };
+
+        let mut words = text.split(is_separator);
+        let first = words.next().unwrap_or_default();
+        let Some(last) = words.next_back() else {
+            // The current word goes on
+            if self.split {
+                self.tail.push_str(first);
+            } else {
+                self.head.push_str(first);
+            }
+            return;
+        };
+        if self.split {
+            self.end_word(first);
+        } else {
+            self.head.push_str(first);
+            self.split = true;
+        }
+        for word in words {
+            self.add(word);
+        }
+        self.tail.push_str(last);
+    }
+
+    fn finish(&mut self) {
+        let head = std::mem::take(&mut self.head);
+        self.add(&head);
+        self.end_word("");
+        self.split = true;
+        self.segments.finish();
+        self.take_segments();
+    }
+
+    fn merge(&mut self, next: Self) {
+        if !next.split {
+            if self.split {
+                self.tail.push_str(&next.head);
+            } else {
+                self.head.push_str(&next.head);
+            }
+        } else {
+            if self.split {
+                self.end_word(&next.head);
+            } else {
+                self.head.push_str(&next.head);
+                self.split = true;
+            }
+            self.tail = next.tail;
+        }
+        for (word, count) in next.counts {
+            *self.counts.entry(word).or_default() += count;
+        }
+        self.segments.merge(next.segments);
+        self.take_segments();
+    }
+}
+
+/// Add up the frequencies of separate inputs, e.g. for a total over
+/// several files
+impl std::ops::AddAssign for Frequencies {
+    fn add_assign(&mut self, other: Self) {
+        for (word, count) in other.counts {
+            *self.counts.entry(word).or_default() += count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(pieces: &[&str], boundary: WordBoundary, ignore_case: bool) -> Frequencies {
+        let mut merged = Frequencies::new(boundary, ignore_case);
+        for piece in pieces {
+            let mut frequencies = Frequencies::new(boundary, ignore_case);
+            frequencies.update(&Chunk::from_text(piece));
+            merged.merge(frequencies);
+        }
+        merged.finish();
+        merged
+    }
+
+    #[test]
+    fn test_top() {
+        let text = "the cat and the hat\nThe end, the END.";
+        let frequencies = count(&[text], WordBoundary::Ascii, false);
+        // Ties are sorted by code point, so uppercase comes first
+        assert_eq!(frequencies.top(2), [("the", 3), ("END.", 1)]);
+        assert_eq!(frequencies.get("end,"), 1);
+        assert_eq!(frequencies.len(), 7);
+
+        let frequencies = count(&[text], WordBoundary::Uax29, true);
+        assert_eq!(frequencies.top(2), [("the", 4), ("end", 2)]);
+        assert_eq!(frequencies.get("END"), 2);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "naïve don't\u{3000}stop, naïve don't stop";
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for boundary in [
+            WordBoundary::Ascii,
+            WordBoundary::Unicode,
+            WordBoundary::Uax29,
+        ] {
+            let expected = count(&[text], boundary, false).counts;
+            for &first in &boundaries {
+                for &second in boundaries.iter().filter(|&&i| i >= first) {
+                    let pieces = [&text[..first], &text[first..second], &text[second..]];
+                    assert_eq!(
+                        count(&pieces, boundary, false).counts,
+                        expected,
+                        "{boundary:?} split at {first} and {second}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_total() {
+        let mut total = count(&["a b"], WordBoundary::Ascii, false);
+        total += count(&["b c"], WordBoundary::Ascii, false);
+        // Words at the edges of separate inputs are never joined
+        assert_eq!(total.top(3), [("b", 2), ("a", 1), ("c", 1)]);
+    }
+
+    #[test]
+    fn test_vocabulary() {
+        let mut total = count(&["the cat and the hat"], WordBoundary::Ascii, false);
+        let vocabulary = total.vocabulary();
+        assert_eq!(vocabulary.distinct, 4);
+        assert_eq!(vocabulary.tokens, 5);
+        assert_eq!(vocabulary.hapax_legomena, 3);
+        assert_eq!(vocabulary.max_word_length, 3);
+        assert!((vocabulary.average_word_length - 3.0).abs() < f64::EPSILON);
+        assert!((vocabulary.type_token_ratio() - 0.8).abs() < f64::EPSILON);
+
+        // Merging the vocabularies, "cat" isn't a hapax legomenon anymore
+        total += count(&["a cat naïvely"], WordBoundary::Ascii, false);
+        let vocabulary = total.vocabulary();
+        assert_eq!(vocabulary.distinct, 6);
+        assert_eq!(vocabulary.tokens, 8);
+        assert_eq!(vocabulary.hapax_legomena, 4);
+        assert_eq!(vocabulary.max_word_length, 7);
+        assert!((vocabulary.average_word_length - 26.0 / 8.0).abs() < f64::EPSILON);
+        assert_eq!(
+            Frequencies::new(WordBoundary::Ascii, false)
+                .vocabulary()
+                .type_token_ratio(),
+            0.0
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use std::io::{self, ErrorKind, Read};
+use std::str::FromStr;
+
+use crate::line_stats::Distribution;
+use crate::metric::{Chunk, Metric};
+use crate::words::{WordBoundary, Words};
+
+const BUF_SIZE: usize = 64 * 1024;
+
+/// A step of a [`JsonPath`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    /// `.name`
+    Key(String),
+    /// `.*`
+    AnyKey,
+    /// `[n]`
+    Index(usize),
+    /// `[]`
+    AnyIndex,
+}
+
+/// A simple path to values in JSON, like `.items[].description`
+///
+/// Made of keys of objects like `.name`, any key `.*`, indexes of arrays
+/// like `[0]`, and any index `[]`. The path `.` is the whole value.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid JSON path: {s} (expected e.g. .items[].description)");
+        match s {
+            "." => return Ok(Self::default()),
+            "" => return Err(invalid()),
+            _ => {}
+        }
+        let mut steps = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                let step = match &after[..end] {
+                    "" => return Err(invalid()),
+                    "*" => Step::AnyKey,
+                    name => Step::Key(name.to_string()),
+                };
+                steps.push(step);
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(invalid)?;
+                steps.push(match &after[..end] {
+                    "" => Step::AnyIndex,
+                    index => Step::Index(index.parse().map_err(|_| invalid())?),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid());
+            }
+        }
+        Ok(Self { steps })
+    }
+}
+
+impl JsonPath {
+    /// Whether a value at `path` is at or below this path
+    fn contains(&self, path: &[Segment]) -> bool {
+        self.steps.len() <= path.len()
+            && self
+                .steps
+                .iter()
+                .zip(path)
+                .all(|(step, segment)| match (step, segment) {
+                    (Step::Key(name), Segment::Key(key)) => name == key,
+                    (Step::Index(index), Segment::Index(other)) => index == other,
+                    (Step::AnyKey, Segment::Key(_)) | (Step::AnyIndex, Segment::Index(_)) => true,
+                    _ => false,
+                })
+    }
+}
+
+/// Strings of JSON, and the records they are in
+///
+/// Each value at the top level is a record, like each line of NDJSON.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct JsonStrings {
+    records: usize,
+    strings: usize,
+    words: Distribution,
+}
+
+impl JsonStrings {
+    pub const fn records(&self) -> usize {
+        self.records
+    }
+
+    /// Number of strings counted, without the keys of objects
+    pub const fn strings(&self) -> usize {
+        self.strings
+    }
+
+    /// Distribution of the words per record
+    pub const fn words(&self) -> &Distribution {
+        &self.words
+    }
+}
+
+/// Add up the strings of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for JsonStrings {
+    fn add_assign(&mut self, other: Self) {
+        self.records += other.records;
+        self.strings += other.strings;
+        self.words += &other.words;
+    }
+}
+
+/// A key of an object or index of an array, on the way to a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Where a value is expected
+    Value,
+    /// Right after `[`, where a value or `]` is expected
+    ArrayStart,
+    /// Right after `{`, where a key or `}` is expected
+    ObjectStart,
+    /// Where a key is expected
+    Key,
+    Colon,
+    /// After a value in an object or array
+    Next,
+    String {
+        key: bool,
+    },
+    Escape {
+        key: bool,
+    },
+    /// Hex digits of an escape like `\u00e9`
+    Unicode {
+        key: bool,
+        digits: u8,
+        code: u32,
+    },
+    /// A number, `true`, `false`, or `null`
+    Literal,
+}
+
+/// Splits JSON into values, a byte at a time
+#[derive(Debug)]
+struct Tokenizer {
+    boundary: WordBoundary,
+    path: Option<JsonPath>,
+    state: State,
+    /// Keys and indexes on the way to the current value
+    segments: Vec<Segment>,
+    /// The string or literal so far
+    string: Vec<u8>,
+    /// First half of a surrogate pair like `\ud83d\ude00`
+    high_surrogate: Option<u32>,
+    /// Words of the current record so far
+    words: Words,
+    strings: JsonStrings,
+    /// Bytes so far, to tell where the JSON is invalid
+    offset: usize,
+    error: Option<String>,
+}
+
+impl Tokenizer {
+    fn new(boundary: WordBoundary) -> Self {
+        Self {
+            boundary,
+            path: None,
+            state: State::Value,
+            segments: Vec::new(),
+            string: Vec::new(),
+            high_surrogate: None,
+            words: Words::new(boundary),
+            strings: JsonStrings::default(),
+            offset: 0,
+            error: None,
+        }
+    }
+
+    fn step(&mut self, byte: u8, text: &mut Vec<u8>) {
+        if self.error.is_some() {
+            return;
+        }
+        self.offset += 1;
+        match self.state {
+            State::String { key } => match byte {
+                b'"' => self.end_string(key, text),
+                b'\\' => self.state = State::Escape { key },
+                _ => self.string.push(byte),
+            },
+            State::Escape { key } => {
+                let c = match byte {
+                    b'"' => '"',
+                    b'\\' => '\\',
+                    b'/' => '/',
+                    b'b' => '\u{8}',
+                    b'f' => '\u{C}',
+                    b'n' => '\n',
+                    b'r' => '\r',
+                    b't' => '\t',
+                    b'u' => {
+                        self.state = State::Unicode {
+                            key,
+                            digits: 0,
+                            code: 0,
+                        };
+                        return;
+                    }
+                    _ => return self.fail(),
+                };
+                self.push_char(c);
+                self.state = State::String { key };
+            }
+            State::Unicode { key, digits, code } => {
+                let Some(digit) = char::from(byte).to_digit(16) else {
+                    return self.fail();
+                };
+                let code = code * 16 + digit;
+                if digits < 3 {
+                    self.state = State::Unicode {
+                        key,
+                        digits: digits + 1,
+                        code,
+                    };
+                    return;
+                }
+                self.state = State::String { key };
+                self.push_code(code);
+            }
+            State::Literal
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'+' | b'.') =>
+            {
+                self.string.push(byte);
+            }
+            State::Literal => {
+                self.end_literal();
+                self.offset -= 1;
+                self.step(byte, text);
+            }
+            _ if byte.is_ascii_whitespace() => {}
+            State::Value | State::ArrayStart => match byte {
+                b']' if self.state == State::ArrayStart => self.close(),
+                b'"' => self.state = State::String { key: false },
+                b'{' => {
+                    self.segments.push(Segment::Key(String::new()));
+                    self.state = State::ObjectStart;
+                }
+                b'[' => {
+                    self.segments.push(Segment::Index(0));
+                    self.state = State::ArrayStart;
+                }
+                b'-' | b'0'..=b'9' | b't' | b'f' | b'n' => {
+                    self.string.push(byte);
+                    self.state = State::Literal;
+                }
+                _ => self.fail(),
+            },
+            State::ObjectStart | State::Key => match byte {
+                b'"' => self.state = State::String { key: true },
+                b'}' if self.state == State::ObjectStart => self.close(),
+                _ => self.fail(),
+            },
+            State::Colon => match byte {
+                b':' => self.state = State::Value,
+                _ => self.fail(),
+            },
+            State::Next => match (self.segments.last_mut(), byte) {
+                (Some(Segment::Key(_)), b',') => self.state = State::Key,
+                (Some(Segment::Index(index)), b',') => {
+                    *index += 1;
+                    self.state = State::Value;
+                }
+                (Some(Segment::Key(_)), b'}') | (Some(Segment::Index(_)), b']') => self.close(),
+                _ => self.fail(),
+            },
+        }
+    }
+
+    fn fail(&mut self) {
+        self.error = Some(format!("Invalid JSON at byte {}", self.offset));
+    }
+
+    fn push_char(&mut self, c: char) {
+        if self.high_surrogate.take().is_some() {
+            self.push_char(char::REPLACEMENT_CHARACTER);
+        }
+        self.string
+            .extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    /// Add the code of a `\u` escape, which may be half of a surrogate pair
+    fn push_code(&mut self, code: u32) {
+        match (self.high_surrogate.take(), code) {
+            (high, 0xD800..=0xDBFF) => {
+                if high.is_some() {
+                    self.push_char(char::REPLACEMENT_CHARACTER);
+                }
+                self.high_surrogate = Some(code);
+            }
+            (Some(high), 0xDC00..=0xDFFF) => {
+                let code = 0x10000 + ((high - 0xD800) << 10) + (code - 0xDC00);
+                self.push_char(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            (high, _) => {
+                if high.is_some() {
+                    self.push_char(char::REPLACEMENT_CHARACTER);
+                }
+                self.push_char(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+        }
+    }
+
+    fn end_string(&mut self, key: bool, text: &mut Vec<u8>) {
+        if self.high_surrogate.take().is_some() {
+            self.push_char(char::REPLACEMENT_CHARACTER);
+        }
+        let string = std::mem::take(&mut self.string);
+        if key {
+            // Keys are only needed to follow a path
+            if let (Some(Segment::Key(name)), Some(_)) = (self.segments.last_mut(), &self.path) {
+                *name = String::from_utf8_lossy(&string).into_owned();
+            }
+            self.state = State::Colon;
+            return;
+        }
+        if self
+            .path
+            .as_ref()
+            .is_none_or(|path| path.contains(&self.segments))
+        {
+            self.strings.strings += 1;
+            self.words
+                .update(&Chunk::from_text(&String::from_utf8_lossy(&string)));
+            self.words.update(&Chunk::from_text("\n"));
+            text.extend_from_slice(&string);
+            text.push(b'\n');
+        }
+        self.string = string;
+        self.string.clear();
+        self.end_value();
+    }
+
+    /// End a number, `true`, `false`, or `null`
+    fn end_literal(&mut self) {
+        let valid = is_literal(&self.string);
+        self.string.clear();
+        if valid {
+            self.end_value();
+        } else {
+            self.fail();
+        }
+    }
+
+    /// Close an object or array
+    fn close(&mut self) {
+        self.segments.pop();
+        self.end_value();
+    }
+
+    fn end_value(&mut self) {
+        if !self.segments.is_empty() {
+            self.state = State::Next;
+            return;
+        }
+        self.state = State::Value;
+        let mut words = std::mem::replace(&mut self.words, Words::new(self.boundary));
+        words.finish();
+        self.strings.records += 1;
+        self.strings.words.add(words.count());
+    }
+
+    fn finish(&mut self) {
+        match self.state {
+            State::Literal if self.segments.is_empty() => self.end_literal(),
+            State::Value if self.segments.is_empty() => {}
+            _ if self.error.is_none() => {
+                self.error = Some("Unexpected end of JSON".to_string());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `literal` is `true`, `false`, `null`, or a number like `-1.5e3`
+fn is_literal(literal: &[u8]) -> bool {
+    if matches!(literal, b"true" | b"false" | b"null") {
+        return true;
+    }
+    let digits = |text: &[u8]| text.iter().take_while(|b| b.is_ascii_digit()).count();
+    let rest = literal.strip_prefix(b"-").unwrap_or(literal);
+    let int = digits(rest);
+    if int == 0 || (int > 1 && rest[0] == b'0') {
+        return false;
+    }
+    let mut rest = &rest[int..];
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let len = digits(fraction);
+        if len == 0 {
+            return false;
+        }
+        rest = &fraction[len..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or_else(|| rest.strip_prefix(b"E")) {
+        let exponent = exponent
+            .strip_prefix(b"+")
+            .or_else(|| exponent.strip_prefix(b"-"))
+            .unwrap_or(exponent);
+        let len = digits(exponent);
+        if len == 0 {
+            return false;
+        }
+        rest = &exponent[len..];
+    }
+    rest.is_empty()
+}
+
+/// The strings of JSON, or of NDJSON with a value on each line, read from
+/// the JSON as it's read
+///
+/// Leaves out the keys of objects, numbers, `true`, `false`, `null`, and
+/// the punctuation, and decodes escapes like `\n` or `\u00e9`. Each
+/// string is written on a line of its own. With a [`JsonPath`], only the
+/// strings at or below the path in each value count. The records, i.e.
+/// values at the top level, and the words of each are counted along the
+/// way.
+///
+/// Reading fails with [`ErrorKind::InvalidData`] if the JSON is invalid.
+#[derive(Debug)]
+pub struct JsonText<R> {
+    input: R,
+    tokenizer: Tokenizer,
+    buffer: Vec<u8>,
+    /// Text from the JSON read so far, of which `start..` is left
+    text: Vec<u8>,
+    start: usize,
+    eof: bool,
+}
+
+impl<R: Read> JsonText<R> {
+    pub fn new(input: R, boundary: WordBoundary) -> Self {
+        Self {
+            input,
+            tokenizer: Tokenizer::new(boundary),
+            buffer: vec![0; BUF_SIZE],
+            text: Vec::new(),
+            start: 0,
+            eof: false,
+        }
+    }
+
+    /// Only count the strings at or below a path
+    #[must_use]
+    pub fn with_path(mut self, path: JsonPath) -> Self {
+        self.tokenizer.path = Some(path);
+        self
+    }
+
+    /// Strings and records read so far
+    pub const fn strings(&self) -> &JsonStrings {
+        &self.tokenizer.strings
+    }
+
+    pub fn into_strings(self) -> JsonStrings {
+        self.tokenizer.strings
+    }
+}
+
+impl<R: Read> Read for JsonText<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.start == self.text.len() {
+            if let Some(error) = self.tokenizer.error.take() {
+                return Err(io::Error::new(ErrorKind::InvalidData, error));
+            }
+            if self.eof {
+                return Ok(0);
+            }
+            self.text.clear();
+            self.start = 0;
+            let bytes_read = match self.input.read(&mut self.buffer) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if bytes_read == 0 {
+                self.tokenizer.finish();
+                self.eof = true;
+            }
+            for &byte in &self.buffer[..bytes_read] {
+                self.tokenizer.step(byte, &mut self.text);
+            }
+        }
+        let len = buf.len().min(self.text.len() - self.start);
+        buf[..len].copy_from_slice(&self.text[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Trickle;
+
+    fn extract(json: &str, path: Option<&str>) -> io::Result<(String, JsonStrings)> {
+        let open = |input| {
+            let reader = JsonText::new(input, WordBoundary::Ascii);
+            match path {
+                Some(path) => reader.with_path(path.parse().unwrap()),
+                None => reader,
+            }
+        };
+        let mut reader = open(Box::new(json.as_bytes()) as Box<dyn Read>);
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut trickled = open(Box::new(Trickle(json.as_bytes())) as Box<dyn Read>);
+        let mut same = String::new();
+        trickled.read_to_string(&mut same)?;
+        assert_eq!(same, text, "{json:?} byte by byte");
+        Ok((text, reader.into_strings()))
+    }
+
+    #[test]
+    fn test_strings() {
+        let json = r#"{"title": "Hello \"world\"", "count": 3, "ok": true,
+            "tags": ["a b", "caf\u00e9 \ud83d\ude00 \ud800"], "none": null, "empty": {}, "list": [],
+            "nested": {"deep": [{"text": "line\none"}]}, "n": -1.5e3}"#;
+        let (text, strings) = extract(json, None).unwrap();
+        assert_eq!(text, "Hello \"world\"\na b\ncafé 😀 \u{FFFD}\nline\none\n");
+        assert_eq!(strings.records(), 1);
+        assert_eq!(strings.strings(), 4);
+        assert_eq!(strings.words().max(), Some(9));
+    }
+
+    #[test]
+    fn test_ndjson() {
+        let ndjson = "{\"text\": \"one two\"}\n{\"text\": \"three\"}\r\n\n\"four five six\"\n42\n";
+        let (text, strings) = extract(ndjson, None).unwrap();
+        assert_eq!(text, "one two\nthree\nfour five six\n");
+        assert_eq!(strings.records(), 4);
+        assert_eq!(strings.strings(), 3);
+        assert_eq!(strings.words().min(), Some(0));
+        assert_eq!(strings.words().max(), Some(3));
+        assert_eq!(strings.words().mean(), Some(1.5));
+    }
+
+    #[test]
+    fn test_path() {
+        let json = r#"{"items": [{"name": "a", "description": "first one"},
+            {"description": "second", "tags": ["x"]}], "description": "not this"}"#;
+        let texts = |path| extract(json, Some(path)).unwrap().0;
+        assert_eq!(texts(".items[].description"), "first one\nsecond\n");
+        assert_eq!(texts(".items[1]"), "second\nx\n");
+        assert_eq!(texts(".*[0].*"), "a\nfirst one\n");
+        assert_eq!(texts(".description"), "not this\n");
+        assert_eq!(texts(".").lines().count(), 5);
+        assert_eq!(texts(".missing"), "");
+        for invalid in ["", "items", ".items[", ".items[x]", "..a"] {
+            assert!(invalid.parse::<JsonPath>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        for json in [
+            "{\"a\" 1}",
+            "[1 2]",
+            "{\"a\": 1",
+            "\"\\x\"",
+            "}",
+            "[1,}",
+            "{\"a\": nonsense, \"b\": \"x y\"}",
+            "{\"a\": 1, \"b\": \"x y\",}",
+        ] {
+            let error = extract(json, None).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{json}");
+        }
+        for literal in [
+            "01", "1.", ".5", "1e", "1e+", "-", "tru", "nulls", "0x1", "nonsense",
+        ] {
+            for json in [literal.to_string(), format!("[{literal}]")] {
+                let error = extract(&json, None).unwrap_err();
+                assert_eq!(error.kind(), ErrorKind::InvalidData, "{json}");
+            }
+        }
+        assert!(extract("[-0, 1.5e+3, 2E-2, 10, true, false, null]", None).is_ok());
+    }
+}
@@ -0,0 +1,274 @@
+//! Line and word counting kernels
+//!
+//! These count newlines and the starts of ASCII-whitespace separated words
+//! in raw bytes, which works for UTF-8 and any other ASCII-compatible
+//! encoding. The vectorized kernels compare a whole block of bytes at once
+//! and count bits in the resulting masks instead of looking at one byte at
+//! a time. All of them return exactly the same counts.
+
+/// Lines and words counted by a kernel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Ascii {
+    pub lines: usize,
+    pub words: usize,
+    /// The last byte was part of a word, so a word continuing in the next
+    /// chunk must not be counted again
+    pub in_word: bool,
+}
+
+/// Implementations of the counting kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kernel {
+    /// Plain byte-at-a-time loop, available everywhere
+    Scalar,
+    /// 16 bytes at a time
+    Sse2,
+    /// 32 bytes at a time
+    Avx2,
+    /// 64 bytes at a time
+    Avx512,
+}
+
+impl Kernel {
+    /// The fastest kernel the current CPU supports
+    pub fn detect() -> Self {
+        Self::available().pop().unwrap_or(Self::Scalar)
+    }
+
+    /// All kernels the current CPU supports, from slowest to fastest
+    pub fn available() -> Vec<Self> {
+        [Self::Scalar, Self::Sse2, Self::Avx2, Self::Avx512]
+            .into_iter()
+            .filter(|kernel| kernel.is_supported())
+            .collect()
+    }
+
+    /// Whether the current CPU supports this kernel
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => is_x86_feature_detected!("avx512bw"),
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => false,
+        }
+    }
+
+    /// Count lines and words in `bytes`, continuing a word if `in_word`
+    ///
+    /// # Panics
+    ///
+    /// If the current CPU doesn't support this kernel
+    pub fn count(self, bytes: &[u8], in_word: bool) -> Ascii {
+        assert!(self.is_supported(), "{self:?} is not supported on this CPU");
+        let mut ascii = Ascii {
+            in_word,
+            ..Ascii::default()
+        };
+        let rest = match self {
+            Self::Scalar => bytes,
+            // SAFETY: the CPU supports the kernel's target features, checked above
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse2 => unsafe { x86::sse2(bytes, &mut ascii) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => unsafe { x86::avx2(bytes, &mut ascii) },
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx512 => unsafe { x86::avx512(bytes, &mut ascii) },
+            #[cfg(not(target_arch = "x86_64"))]
+            _ => unreachable!(),
+        };
+        // Whatever doesn't fill a whole block
+        scalar(rest, &mut ascii);
+        ascii
+    }
+}
+
+fn scalar(bytes: &[u8], ascii: &mut Ascii) {
+    for &byte in bytes {
+        match byte {
+            b'\n' => {
+                ascii.lines += 1;
+                ascii.in_word = false;
+            }
+            _ if byte.is_ascii_whitespace() => ascii.in_word = false,
+            _ => {
+                if !ascii.in_word {
+                    ascii.words += 1;
+                    ascii.in_word = true;
+                }
+            }
+        }
+    }
+}
+
+/// Count one block, given a bit mask of its whitespace and newline bytes
+/// (bit `i` stands for byte `i`)
+#[inline(always)]
+fn count_block(whitespace: u64, newlines: u64, width: u32, ascii: &mut Ascii) {
+    // A word starts at every non-whitespace byte preceded by whitespace
+    let preceded_by_whitespace = (whitespace << 1) | u64::from(!ascii.in_word);
+    let mut starts = !whitespace & preceded_by_whitespace;
+    if width < u64::BITS {
+        starts &= (1 << width) - 1;
+    }
+    ascii.lines += newlines.count_ones() as usize;
+    ascii.words += starts.count_ones() as usize;
+    ascii.in_word = whitespace & (1 << (width - 1)) == 0;
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{count_block, Ascii};
+    use std::arch::x86_64::*;
+
+    // ASCII whitespace: space, tab, newline, form feed, and carriage return
+    const WHITESPACE: [u8; 5] = [b' ', b'\t', b'\n', b'\x0C', b'\r'];
+
+    /// Count all whole 16-byte blocks and return the rest
+    #[target_feature(enable = "sse2")]
+    pub(super) unsafe fn sse2<'a>(bytes: &'a [u8], ascii: &mut Ascii) -> &'a [u8] {
+        let blocks = bytes.chunks_exact(16);
+        let rest = blocks.remainder();
+        let [space, tab, newline, form_feed, carriage_return] =
+            WHITESPACE.map(|byte| _mm_set1_epi8(byte as i8));
+        for block in blocks {
+            let block = _mm_loadu_si128(block.as_ptr().cast());
+            let newlines = _mm_cmpeq_epi8(block, newline);
+            let whitespace = _mm_or_si128(
+                _mm_or_si128(_mm_cmpeq_epi8(block, space), _mm_cmpeq_epi8(block, tab)),
+                _mm_or_si128(
+                    _mm_or_si128(newlines, _mm_cmpeq_epi8(block, form_feed)),
+                    _mm_cmpeq_epi8(block, carriage_return),
+                ),
+            );
+            count_block(
+                u64::from(_mm_movemask_epi8(whitespace) as u16),
+                u64::from(_mm_movemask_epi8(newlines) as u16),
+                16,
+                ascii,
+            );
+        }
+        rest
+    }
+
+    /// Count all whole 32-byte blocks and return the rest
+    #[target_feature(enable = "avx2")]
+    pub(super) unsafe fn avx2<'a>(bytes: &'a [u8], ascii: &mut Ascii) -> &'a [u8] {
+        let blocks = bytes.chunks_exact(32);
+        let rest = blocks.remainder();
+        let [space, tab, newline, form_feed, carriage_return] =
+            WHITESPACE.map(|byte| _mm256_set1_epi8(byte as i8));
+        for block in blocks {
+            let block = _mm256_loadu_si256(block.as_ptr().cast());
+            let newlines = _mm256_cmpeq_epi8(block, newline);
+            let whitespace = _mm256_or_si256(
+                _mm256_or_si256(
+                    _mm256_cmpeq_epi8(block, space),
+                    _mm256_cmpeq_epi8(block, tab),
+                ),
+                _mm256_or_si256(
+                    _mm256_or_si256(newlines, _mm256_cmpeq_epi8(block, form_feed)),
+                    _mm256_cmpeq_epi8(block, carriage_return),
+                ),
+            );
+            count_block(
+                u64::from(_mm256_movemask_epi8(whitespace) as u32),
+                u64::from(_mm256_movemask_epi8(newlines) as u32),
+                32,
+                ascii,
+            );
+        }
+        rest
+    }
+
+    /// Count all whole 64-byte blocks and return the rest
+    #[target_feature(enable = "avx512bw")]
+    pub(super) unsafe fn avx512<'a>(bytes: &'a [u8], ascii: &mut Ascii) -> &'a [u8] {
+        let blocks = bytes.chunks_exact(64);
+        let rest = blocks.remainder();
+        let [space, tab, newline, form_feed, carriage_return] =
+            WHITESPACE.map(|byte| _mm512_set1_epi8(byte as i8));
+        for block in blocks {
+            let block = _mm512_loadu_si512(block.as_ptr().cast());
+            let newlines = _mm512_cmpeq_epi8_mask(block, newline);
+            let whitespace = _mm512_cmpeq_epi8_mask(block, space)
+                | _mm512_cmpeq_epi8_mask(block, tab)
+                | newlines
+                | _mm512_cmpeq_epi8_mask(block, form_feed)
+                | _mm512_cmpeq_epi8_mask(block, carriage_return);
+            count_block(whitespace, newlines, 64, ascii);
+        }
+        rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random bytes, heavy on whitespace and
+    /// look-alikes like vertical tab
+    fn noise(len: usize, seed: u64) -> Vec<u8> {
+        const ALPHABET: &[u8] = b"  \t\n\r\x0B\x0Cab.\xC3\xA9\xFF";
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state
+                    .wrapping_mul(6_364_136_223_846_793_005)
+                    .wrapping_add(1_442_695_040_888_963_407);
+                ALPHABET[(state >> 33) as usize % ALPHABET.len()]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_kernels_agree() {
+        let mut expected_words = 0;
+        for len in [0, 1, 15, 16, 17, 31, 32, 33, 63, 64, 65, 200, 1000, 4099] {
+            for seed in 0..8 {
+                let bytes = noise(len, seed);
+                for in_word in [false, true] {
+                    let expected = Kernel::Scalar.count(&bytes, in_word);
+                    expected_words += expected.words;
+                    for kernel in Kernel::available() {
+                        assert_eq!(
+                            kernel.count(&bytes, in_word),
+                            expected,
+                            "{kernel:?}, len {len}, seed {seed}"
+                        );
+                    }
+                }
+            }
+        }
+        assert!(expected_words > 0);
+    }
+
+    #[test]
+    fn test_kernels_across_chunks() {
+        let bytes = noise(777, 42);
+        let expected = Kernel::Scalar.count(&bytes, false);
+        for kernel in Kernel::available() {
+            for split in [1, 16, 33, 64, 100] {
+                let (head, tail) = bytes.split_at(split);
+                let first = kernel.count(head, false);
+                let second = kernel.count(tail, first.in_word);
+                assert_eq!(first.lines + second.lines, expected.lines);
+                assert_eq!(first.words + second.words, expected.words, "{kernel:?}");
+                assert_eq!(second.in_word, expected.in_word);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scalar() {
+        let ascii = Kernel::Scalar.count(b"one two\nthree\x0Bfour\r\n", false);
+        assert_eq!(ascii.lines, 2);
+        // Vertical tab is not ASCII whitespace
+        assert_eq!(ascii.words, 3);
+        assert!(!ascii.in_word);
+    }
+}
@@ -0,0 +1,850 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::path::PathBuf;
+
+use crate::metric::{Chunk, Metric};
+use crate::resume::{Resumable, Resume};
+
+/// How deeply `\input` and `\include` can be nested, which stops cycles
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Sectioning commands, whose argument is a heading
+const HEADINGS: &[&str] = &[
+    "part",
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+    "subparagraph",
+];
+
+/// Commands with arguments that aren't text, and how many of them
+const SKIPPED_ARGUMENTS: &[(&str, usize)] = &[
+    ("addbibresource", 1),
+    ("addtocounter", 2),
+    ("addtolength", 2),
+    ("autoref", 1),
+    ("bibliography", 1),
+    ("bibliographystyle", 1),
+    ("cite", 1),
+    ("citealt", 1),
+    ("citeauthor", 1),
+    ("citep", 1),
+    ("citet", 1),
+    ("citeyear", 1),
+    ("color", 1),
+    ("cref", 1),
+    ("Cref", 1),
+    ("DeclareMathOperator", 2),
+    ("documentclass", 1),
+    ("eqref", 1),
+    ("geometry", 1),
+    ("graphicspath", 1),
+    ("hspace", 1),
+    ("href", 1),
+    ("hypersetup", 1),
+    ("include", 1),
+    ("includegraphics", 1),
+    ("index", 1),
+    ("input", 1),
+    ("label", 1),
+    ("linespread", 1),
+    ("newcommand", 2),
+    ("newenvironment", 3),
+    ("newtheorem", 2),
+    ("nocite", 1),
+    ("pagenumbering", 1),
+    ("pageref", 1),
+    ("pagestyle", 1),
+    ("providecommand", 2),
+    ("ref", 1),
+    ("renewcommand", 2),
+    ("renewenvironment", 3),
+    ("RequirePackage", 1),
+    ("setcounter", 2),
+    ("setlength", 2),
+    ("textcolor", 1),
+    ("thispagestyle", 1),
+    ("url", 1),
+    ("usepackage", 1),
+    ("vspace", 1),
+];
+
+/// Environments whose content is math
+const MATH_ENVIRONMENTS: &[&str] = &[
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "displaymath",
+    "eqnarray",
+    "eqnarray*",
+    "equation",
+    "equation*",
+    "flalign",
+    "flalign*",
+    "gather",
+    "gather*",
+    "math",
+    "multline",
+    "multline*",
+];
+
+/// Environments whose content is skipped as it is, up to their end
+const SKIPPED_ENVIRONMENTS: &[&str] = &[
+    "comment",
+    "lstlisting",
+    "minted",
+    "thebibliography",
+    "tikzpicture",
+    "verbatim",
+    "verbatim*",
+    "Verbatim",
+];
+
+/// Environments with arguments that aren't text, like the column
+/// specification of a table, and how many of them
+const ENVIRONMENT_ARGUMENTS: &[(&str, usize)] = &[
+    ("minipage", 1),
+    ("multicols", 1),
+    ("tabular", 1),
+    ("tabular*", 2),
+    ("tabularx", 2),
+    ("wrapfigure", 2),
+];
+
+/// Words and other things counted in a LaTeX document
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TexCounts {
+    /// Words of the body text
+    pub text: usize,
+    /// Words in headings like `\section{...}`
+    pub heading_words: usize,
+    /// Words in captions like `\caption{...}`
+    pub caption_words: usize,
+    /// Words in footnotes like `\footnote{...}` or `\footnotetext{...}`
+    pub footnote_words: usize,
+    /// Number of headings
+    pub headings: usize,
+    /// Formulas in the text, like `$x$` or `\(x\)`
+    pub inline_math: usize,
+    /// Formulas set apart, like `\[x\]` or an `equation` environment
+    pub display_math: usize,
+}
+
+impl TexCounts {
+    /// All words, whatever they are part of
+    pub const fn words(&self) -> usize {
+        self.text + self.heading_words + self.caption_words + self.footnote_words
+    }
+
+    /// Counted since `earlier`, a count of the same text so far
+    const fn since(self, earlier: Self) -> Self {
+        Self {
+            text: self.text - earlier.text,
+            heading_words: self.heading_words - earlier.heading_words,
+            caption_words: self.caption_words - earlier.caption_words,
+            footnote_words: self.footnote_words - earlier.footnote_words,
+            headings: self.headings - earlier.headings,
+            inline_math: self.inline_math - earlier.inline_math,
+            display_math: self.display_math - earlier.display_math,
+        }
+    }
+}
+
+/// Add up the counts of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for TexCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.text += other.text;
+        self.heading_words += other.heading_words;
+        self.caption_words += other.caption_words;
+        self.footnote_words += other.footnote_words;
+        self.headings += other.headings;
+        self.inline_math += other.inline_math;
+        self.display_math += other.display_math;
+    }
+}
+
+/// What the words in a group are part of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Text,
+    Heading,
+    Caption,
+    Footnote,
+    /// Not words at all, like a label or the arguments of a definition
+    Skip,
+}
+
+/// How the current formula ends
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Math {
+    Dollar,
+    DoubleDollar,
+    /// `\)`
+    Paren,
+    /// `\]`
+    Bracket,
+    /// `\end{name}`
+    Environment(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Between `\documentclass` and `\begin{document}`
+    Preamble,
+    Document,
+    /// After `\end{document}`
+    Ended,
+}
+
+/// A group in braces, or the brackets of an optional argument
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Group {
+    category: Category,
+    closer: char,
+}
+
+/// Tells words from markup line by line
+#[derive(Debug, Clone)]
+struct Parser {
+    mode: Mode,
+    groups: Vec<Group>,
+    /// Categories of the arguments the last command still expects, the
+    /// next one last
+    pending: Vec<Category>,
+    /// The last command may still have options in brackets
+    options: bool,
+    /// How many groups the last command is in
+    arguments_at: usize,
+    math: Option<Math>,
+    /// Environment like `verbatim` whose content is skipped
+    skipped: Option<String>,
+    /// Category of the current word, if there is one
+    word: Option<Category>,
+    /// The current word has a letter or digit, so it counts
+    word_counts: bool,
+    /// The current line so far
+    line: String,
+    counts: TexCounts,
+}
+
+impl Parser {
+    fn new() -> Self {
+        Self {
+            mode: Mode::Document,
+            groups: Vec::new(),
+            pending: Vec::new(),
+            options: false,
+            arguments_at: 0,
+            math: None,
+            skipped: None,
+            word: None,
+            word_counts: false,
+            line: String::new(),
+            counts: TexCounts::default(),
+        }
+    }
+}
+
+impl Resume for Parser {
+    fn update(&mut self, text: &str) {
+        let mut rest = text;
+        while let Some(end) = rest.find('\n') {
+            let mut line = std::mem::take(&mut self.line);
+            line.push_str(&rest[..end]);
+            self.add(&line);
+            line.clear();
+            self.line = line;
+            rest = &rest[end + 1..];
+        }
+        self.line.push_str(rest);
+    }
+
+    fn finish(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        self.add(&line);
+    }
+
+    fn resume(&mut self, next: Self, end: &Self) {
+        let counts = next.counts.since(end.counts);
+        *self = Self {
+            counts: self.counts,
+            ..next
+        };
+        self.counts += counts;
+    }
+}
+
+impl Parser {
+    fn category(&self) -> Category {
+        self.groups
+            .last()
+            .map_or(Category::Text, |group| group.category)
+    }
+
+    fn word_char(&mut self, c: char) {
+        if self.word.is_none() {
+            self.word = Some(self.category());
+            self.word_counts = false;
+        }
+        self.word_counts |= c.is_alphanumeric();
+    }
+
+    fn end_word(&mut self) {
+        let count = match self.word.take() {
+            _ if !self.word_counts => return,
+            Some(Category::Text) => &mut self.counts.text,
+            Some(Category::Heading) => &mut self.counts.heading_words,
+            Some(Category::Caption) => &mut self.counts.caption_words,
+            Some(Category::Footnote) => &mut self.counts.footnote_words,
+            Some(Category::Skip) | None => return,
+        };
+        *count += 1;
+        self.word_counts = false;
+    }
+
+    /// Enter a group, which ends the current word if it's part of
+    /// something else
+    fn open(&mut self, category: Category, closer: char) {
+        if category != self.category() {
+            self.end_word();
+        }
+        self.groups.push(Group { category, closer });
+    }
+
+    fn close(&mut self) {
+        if let Some(group) = self.groups.pop() {
+            if group.category != self.category() {
+                self.end_word();
+            }
+        }
+    }
+
+    /// Count a line, without its line break
+    fn add(&mut self, line: &str) {
+        let mut i = 0;
+        let mut commented = false;
+        while i < line.len() {
+            let rest = &line[i..];
+            if let Some(name) = &self.skipped {
+                let end = format!("\\end{{{name}}}");
+                let Some(j) = rest.find(&end) else {
+                    break;
+                };
+                self.skipped = None;
+                i += j + end.len();
+                continue;
+            }
+            match self.mode {
+                Mode::Ended => break,
+                Mode::Preamble => {
+                    let begin = "\\begin{document}";
+                    let Some(j) = rest.find(begin) else {
+                        break;
+                    };
+                    self.mode = Mode::Document;
+                    i += j + begin.len();
+                    continue;
+                }
+                Mode::Document => {}
+            }
+            if self.math.is_some() {
+                let (len, comment) = self.skip_math(rest);
+                i += len;
+                commented |= comment;
+                continue;
+            }
+
+            let Some(c) = rest.chars().next() else {
+                break;
+            };
+            i += c.len_utf8();
+            let arguments = self.groups.len() == self.arguments_at;
+            if arguments && !(c.is_whitespace() || matches!(c, '*' | '[' | '{')) {
+                self.pending.clear();
+                self.options = false;
+            }
+            match c {
+                '%' => {
+                    commented = true;
+                    break;
+                }
+                '\\' => i += self.command(&line[i..]),
+                '{' => {
+                    let category = match self.pending.pop() {
+                        Some(category) if arguments => category,
+                        _ => self.category(),
+                    };
+                    self.open(category, '}');
+                }
+                '[' if arguments && (self.options || !self.pending.is_empty()) => {
+                    self.open(Category::Skip, ']');
+                }
+                '}' | ']' if self.groups.last().is_some_and(|group| group.closer == c) => {
+                    self.close();
+                }
+                '$' if self.category() != Category::Skip => {
+                    self.end_word();
+                    if line[i..].starts_with('$') {
+                        i += 1;
+                        self.math = Some(Math::DoubleDollar);
+                        self.counts.display_math += 1;
+                    } else {
+                        self.math = Some(Math::Dollar);
+                        self.counts.inline_math += 1;
+                    }
+                }
+                '~' => self.end_word(),
+                _ if c.is_whitespace() => self.end_word(),
+                _ => self.word_char(c),
+            }
+        }
+        // A comment joins the line with the next one
+        if !commented {
+            self.end_word();
+        }
+    }
+
+    /// Skip math up to its end, or the end of the line, and return how
+    /// much of `text` was skipped, and whether the rest is a comment
+    fn skip_math(&mut self, text: &str) -> (usize, bool) {
+        let Some(math) = &self.math else {
+            return (0, false);
+        };
+        let mut chars = text.char_indices();
+        while let Some((i, c)) = chars.next() {
+            let after = chars.as_str();
+            let end = match (c, math) {
+                ('%', _) => return (text.len(), true),
+                ('$', Math::Dollar) => Some(1),
+                ('$', Math::DoubleDollar) if after.starts_with('$') => Some(2),
+                ('\\', Math::Paren) if after.starts_with(')') => Some(2),
+                ('\\', Math::Bracket) if after.starts_with(']') => Some(2),
+                ('\\', Math::Environment(name))
+                    if after
+                        .strip_prefix("end{")
+                        .and_then(|after| after.strip_prefix(name.as_str()))
+                        .is_some_and(|after| after.starts_with('}')) =>
+                {
+                    Some(name.len() + 6)
+                }
+                // Escaped characters like `\$` or `\%`
+                ('\\', _) => {
+                    chars.next();
+                    None
+                }
+                _ => None,
+            };
+            if let Some(len) = end {
+                self.math = None;
+                return (i + len, false);
+            }
+        }
+        (text.len(), false)
+    }
+
+    /// Handle the command `text` starts with, right after the backslash,
+    /// and return its length
+    fn command(&mut self, text: &str) -> usize {
+        let letters = text.bytes().take_while(u8::is_ascii_alphabetic).count();
+        let len = if letters > 0 {
+            letters
+        } else {
+            text.chars().next().map_or(0, char::len_utf8)
+        };
+        let name = &text[..len];
+        // Groups that aren't text are only skipped
+        if self.category() == Category::Skip {
+            return len;
+        }
+        match name {
+            // Escaped characters
+            "%" | "&" | "$" | "#" | "_" | "{" | "}" => {
+                self.word_char(name.chars().next().unwrap_or_default());
+            }
+            // Accents like `\'e`, hyphenation hints, and italic corrections
+            // are part of the word
+            "'" | "`" | "^" | "\"" | "~" | "=" | "." | "-" | "/" | "" => {}
+            "(" => {
+                self.end_word();
+                self.math = Some(Math::Paren);
+                self.counts.inline_math += 1;
+            }
+            "[" => {
+                self.end_word();
+                self.math = Some(Math::Bracket);
+                self.counts.display_math += 1;
+            }
+            "begin" | "end" => {
+                self.end_word();
+                return len + self.environment(name == "begin", &text[len..]);
+            }
+            _ => {
+                self.end_word();
+                self.arguments_at = self.groups.len();
+                self.options = true;
+                self.pending = match name {
+                    "caption" => vec![Category::Caption],
+                    "footnote" | "footnotetext" => vec![Category::Footnote],
+                    _ if HEADINGS.contains(&name) => {
+                        self.counts.headings += 1;
+                        vec![Category::Heading]
+                    }
+                    _ => {
+                        if name == "documentclass" {
+                            self.mode = Mode::Preamble;
+                        }
+                        let skipped = SKIPPED_ARGUMENTS
+                            .iter()
+                            .find(|(command, _)| *command == name)
+                            .map_or(0, |&(_, count)| count);
+                        vec![Category::Skip; skipped]
+                    }
+                };
+            }
+        }
+        len
+    }
+
+    /// Handle the start or end of the environment named in `text`, and
+    /// return the length of its name in braces
+    fn environment(&mut self, begin: bool, text: &str) -> usize {
+        let Some((name, len)) = text
+            .strip_prefix('{')
+            .and_then(|rest| rest.find('}').map(|end| (&rest[..end], end + 2)))
+        else {
+            return 0;
+        };
+        match (begin, name) {
+            (true, "document") => self.mode = Mode::Document,
+            (false, "document") => self.mode = Mode::Ended,
+            (true, _) if MATH_ENVIRONMENTS.contains(&name) => {
+                self.math = Some(Math::Environment(name.to_string()));
+                self.counts.display_math += 1;
+            }
+            (true, _) if SKIPPED_ENVIRONMENTS.contains(&name) => {
+                self.skipped = Some(name.to_string());
+            }
+            (true, _) => {
+                self.arguments_at = self.groups.len();
+                self.options = true;
+                let skipped = ENVIRONMENT_ARGUMENTS
+                    .iter()
+                    .find(|(environment, _)| *environment == name)
+                    .map_or(0, |&(_, count)| count);
+                self.pending = vec![Category::Skip; skipped];
+            }
+            (false, _) => {}
+        }
+        len
+    }
+}
+
+/// Words of LaTeX source, in the spirit of `texcount`
+///
+/// Leaves out commands, comments, math, the preamble between
+/// `\documentclass` and `\begin{document}`, and environments like
+/// `verbatim`. Words in headings, captions, and footnotes are counted
+/// apart from the body text. The arguments of most commands count as
+/// text, except for known ones like `\cite` or `\label`, and options in
+/// brackets are skipped. Words are runs of characters between whitespace
+/// or commands with at least one letter or digit in them.
+///
+/// Like for [`CodeLines`](crate::CodeLines), pieces of the input counted
+/// on their own assume they start a document and keep their start to
+/// count again once the text before is known.
+#[derive(Debug, Clone)]
+pub struct Latex {
+    parser: Resumable<Parser>,
+}
+
+impl Default for Latex {
+    fn default() -> Self {
+        Self {
+            parser: Resumable::new(Parser::new()),
+        }
+    }
+}
+
+impl Latex {
+    pub const fn counts(&self) -> TexCounts {
+        self.parser.get().counts
+    }
+}
+
+impl Metric for Latex {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        self.parser.update(text);
+    }
+
+    fn finish(&mut self) {
+        self.parser.finish();
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.parser.merge(next.parser);
+    }
+}
+
+/// Add up the counts of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for Latex {
+    fn add_assign(&mut self, other: Self) {
+        self.parser.get_mut().counts += other.counts();
+    }
+}
+
+/// A file included with `\input` or `\include`, and the rest of the line
+/// that included the next one
+struct Source {
+    reader: Box<dyn BufRead>,
+    rest: Vec<u8>,
+}
+
+/// LaTeX source with the files it includes with `\input{...}` or
+/// `\include{...}` in place of the commands, read as it's read
+///
+/// Like LaTeX does, files are looked for relative to the directory of the
+/// main document, trying with a `.tex` extension first. Commands in
+/// comments don't include anything. Like texcount, files that don't exist
+/// are left out with a warning on standard error.
+pub struct TexInput {
+    /// Directory of the main document
+    dir: PathBuf,
+    /// The main document and the files it includes, the one being read
+    /// last
+    sources: Vec<Source>,
+    /// Text read so far, of which `start..` is left
+    text: Vec<u8>,
+    start: usize,
+}
+
+impl TexInput {
+    pub fn new(input: impl Read + 'static, dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            sources: vec![Source {
+                reader: Box::new(BufReader::new(input)),
+                rest: Vec::new(),
+            }],
+            text: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// Read the next part of the source into `text`, or nothing at the end
+    fn fill(&mut self) -> io::Result<()> {
+        while let Some(source) = self.sources.last_mut() {
+            let mut line = std::mem::take(&mut source.rest);
+            if line.is_empty() {
+                match source.reader.read_until(b'\n', &mut line) {
+                    Ok(0) => {
+                        self.sources.pop();
+                        continue;
+                    }
+                    Ok(_) => {}
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => return Err(e),
+                }
+            }
+            let Some((start, name, end)) = find_include(&line) else {
+                self.text.extend_from_slice(&line);
+                return Ok(());
+            };
+            self.text.extend_from_slice(&line[..start]);
+            source.rest = line[end..].to_vec();
+            let reader = match self.open(&String::from_utf8_lossy(name)) {
+                Ok(reader) => reader,
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    eprintln!("Warning: {e}, left out");
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            self.sources.push(Source {
+                reader: Box::new(BufReader::new(reader)),
+                rest: Vec::new(),
+            });
+            if !self.text.is_empty() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn open(&self, name: &str) -> io::Result<File> {
+        if self.sources.len() > MAX_INCLUDE_DEPTH {
+            return Err(io::Error::other(format!(
+                "Includes are nested too deeply at {name}"
+            )));
+        }
+        let with_extension = self.dir.join(format!("{name}.tex"));
+        let path = if !name.ends_with(".tex") && with_extension.is_file() {
+            with_extension
+        } else {
+            self.dir.join(name)
+        };
+        File::open(&path).map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+    }
+}
+
+impl Read for TexInput {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.start == self.text.len() {
+            self.text.clear();
+            self.start = 0;
+            self.fill()?;
+        }
+        let len = buf.len().min(self.text.len() - self.start);
+        buf[..len].copy_from_slice(&self.text[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+/// Where the first `\input{name}` or `\include{name}` in a line starts,
+/// its name, and where it ends, unless it's in a comment
+fn find_include(line: &[u8]) -> Option<(usize, &[u8], usize)> {
+    let mut i = 0;
+    while i < line.len() {
+        match line[i] {
+            b'%' => return None,
+            b'\\' => {
+                let rest = &line[i + 1..];
+                let command = [&b"input{"[..], b"include{"]
+                    .into_iter()
+                    .find(|command| rest.starts_with(command));
+                if let Some(command) = command {
+                    let name_start = i + 1 + command.len();
+                    let name_len = line[name_start..].iter().position(|&b| b == b'}')?;
+                    let name = &line[name_start..name_start + name_len];
+                    return Some((i, name.trim_ascii(), name_start + name_len + 1));
+                }
+                // Skip escaped characters like `\%`
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resume::MAX_HEAD_LEN;
+
+    fn count(pieces: &[&str]) -> TexCounts {
+        let mut merged = Latex::default();
+        for piece in pieces {
+            let mut latex = Latex::default();
+            latex.update(&Chunk::from_text(piece));
+            merged.merge(latex);
+        }
+        merged.finish();
+        merged.counts()
+    }
+
+    #[test]
+    fn test_words() {
+        let text = "\\documentclass[a4paper]{article}\n\
+                    \\usepackage{amsmath} % not counted\n\
+                    \\title{Not counted}\n\
+                    \\begin{document}\n\
+                    \\section*{An Introduction}\\label{sec:intro}\n\
+                    Some \\emph{important} text~\\cite{knuth84}, 100\\% caf\\'e -- done.\n\
+                    Math $x^2 + y$ and \\(a\\) or \\[ b \\] and\n\
+                    \\begin{equation}\n  E = mc^2 % comment\n\\end{equation}\n\
+                    \\begin{figure}[h]\\includegraphics[width=3cm]{a.png}\n\
+                    \\caption[Short]{A caption.}\\end{figure}\n\
+                    Foot\\footnote{See \\url{https://example.com} there.} note.\n\
+                    \\begin{verbatim}\nnot $ counted\n\\end{verbatim}\n\
+                    \\begin{tabular}{ll} a & b \\\\ \\end{tabular}\n\
+                    hyphen%\nated\n\
+                    \\end{document}\nnot counted";
+        assert_eq!(
+            count(&[text]),
+            TexCounts {
+                text: 15,
+                heading_words: 2,
+                caption_words: 2,
+                footnote_words: 2,
+                headings: 1,
+                inline_math: 2,
+                display_math: 2,
+            }
+        );
+        assert_eq!(count(&[text]).words(), 21);
+        // Math with characters outside of ASCII
+        let math = count(&["Angle $α$ of \\(90°\\) and $$π$$ or \\[ ½ \\] — ok"]);
+        assert_eq!((math.text, math.inline_math, math.display_math), (5, 2, 2));
+        // Without a preamble, everything is part of the document
+        assert_eq!(count(&["A chapter {of} text"]).text, 4);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "\\documentclass{x}\n\\begin{document}\n\\section{Hi there}\n\
+                    A $b$ c\\footnote{d \\ref{e}} \\[f\n\\] g%\nh\n\\end{document}";
+        let expected = count(&[text]);
+        assert_eq!(expected.words(), 6);
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first) {
+                let pieces = [&text[..first], &text[first..second], &text[second..]];
+                assert_eq!(count(&pieces), expected, "split at {first} and {second}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_head() {
+        // Counted in two chunks, so only the first is counted again
+        let long = "x\n".repeat(MAX_HEAD_LEN);
+        let text = format!("\\section{{A}}\n{long}\\footnote{{B}} $c$\n{long}");
+        let expected = count(&[&text]);
+        let (first, second) = text.split_at(MAX_HEAD_LEN);
+        let mut merged = Latex::default();
+        merged.update(&Chunk::from_text(first));
+        let mut next = Latex::default();
+        next.update(&Chunk::from_text(&second[..MAX_HEAD_LEN + 1000]));
+        next.update(&Chunk::from_text(&second[MAX_HEAD_LEN + 1000..]));
+        merged.merge(next);
+        merged.finish();
+        assert_eq!(merged.counts(), expected);
+        assert_eq!(expected.footnote_words, 1);
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("wc-latex-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("chapters")).unwrap();
+        std::fs::write(dir.join("chapters/one.tex"), "one \\input{two.tex} four\n").unwrap();
+        std::fs::write(dir.join("two.tex"), "two three").unwrap();
+        std::fs::write(dir.join("cycle.tex"), "\\input{cycle}").unwrap();
+
+        let main = "a \\include{chapters/one} b % \\input{missing}\n\\input{";
+        let mut text = String::new();
+        TexInput::new(main.as_bytes(), &dir)
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(
+            text,
+            "a one two three four\n b % \\input{missing}\n\\input{"
+        );
+
+        let mut missing = TexInput::new(&b"a \\input{missing} b\n"[..], &dir);
+        text.clear();
+        missing.read_to_string(&mut text).unwrap();
+        assert_eq!(text, "a  b\n");
+        let mut cycle = TexInput::new(&b"\\input{cycle}"[..], &dir);
+        assert!(cycle.read_to_string(&mut text).is_err());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
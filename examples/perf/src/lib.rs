@@ -1,13 +1,80 @@
-use std::io::{BufReader, Read};
+use std::io::{ErrorKind, Read};
+
+mod classes;
+mod code;
+mod delimited;
+mod encoding;
+mod frequency;
+mod json;
+pub mod kernel;
+mod latex;
+mod line_stats;
+mod markdown;
+mod markup;
+pub mod metric;
+mod ngram;
+mod prose;
+mod readability;
+mod resume;
+mod segments;
+mod split;
+mod subtitles;
+#[cfg(test)]
+mod testing;
+mod words;
+
+pub use classes::{CharClass, CharClasses};
+pub use code::{CodeLines, Language, SourceLines};
+pub use delimited::{Column, ColumnCounts, DelimitedText, RaggedRecord, Records};
+pub use encoding::Encoding;
+use encoding::{Decoded, MAX_BOM_LEN};
+pub use frequency::{Frequencies, Vocabulary};
+pub use json::{JsonPath, JsonStrings, JsonText};
+pub use latex::{Latex, TexCounts, TexInput};
+pub use line_stats::{Distribution, LineStats};
+pub use markdown::{CodeBlocks, Markdown, Section};
+pub use markup::{ElementCounts, Elements, Markup, MarkupText};
+pub use metric::Metric;
+use metric::{Bytes, Chars, Chunk, Graphemes, Lines, MaxLineWidth};
+pub use ngram::NGrams;
+pub use prose::{Paragraphs, Sentences};
+pub use readability::{syllables, Readability, Scores};
+pub use split::{Headings, SectionSplitter, SectionText};
+pub use subtitles::{Cue, CueTotals, SubtitleText, Subtitles};
+pub use words::{WordBoundary, Words};
 
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
 /// Counts for words, lines, and characters
-#[derive(Debug, Clone, Copy)]
+///
+/// "Characters" come in three flavors, which only agree on ASCII text:
+/// raw bytes (`wc -c`), Unicode scalar values (`wc -m`) and extended
+/// grapheme clusters, i.e. what a reader perceives as a single character.
+#[derive(Debug, Default, Clone, Copy)]
 pub struct Counts {
     pub words: usize,
     pub lines: usize,
-    pub characters: usize,
+    /// Sentences, which end with terminal punctuation or a paragraph break
+    pub sentences: usize,
+    /// Runs of lines with text, separated by blank lines
+    pub paragraphs: usize,
+    pub bytes: usize,
+    pub chars: usize,
+    pub graphemes: usize,
+    /// Display width of the longest line (`wc -L`), in terminal columns
+    pub max_line_width: usize,
+    /// 1-based number of the longest line within the input it came from,
+    /// or 0 if there are no lines at all
+    pub longest_line: usize,
+    /// Number of invalid UTF-8 sequences, each counted as one U+FFFD
+    /// replacement character
+    pub invalid_sequences: usize,
+    /// Byte offset of the first invalid UTF-8 sequence within the input
+    /// it came from
+    pub first_invalid: Option<usize>,
+    /// Encoding the input was decoded from, or `None` for totals over
+    /// inputs in different encodings
+    pub encoding: Option<Encoding>,
 }
 
 // Implement addition for Counts
@@ -18,7 +85,24 @@ impl std::ops::Add for Counts {
         Self {
             words: self.words + other.words,
             lines: self.lines + other.lines,
-            characters: self.characters + other.characters,
+            sentences: self.sentences + other.sentences,
+            paragraphs: self.paragraphs + other.paragraphs,
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            graphemes: self.graphemes + other.graphemes,
+            // Keep the first of the longest lines
+            max_line_width: self.max_line_width.max(other.max_line_width),
+            longest_line: if other.max_line_width > self.max_line_width || self.longest_line == 0 {
+                other.longest_line
+            } else {
+                self.longest_line
+            },
+            invalid_sequences: self.invalid_sequences + other.invalid_sequences,
+            first_invalid: self.first_invalid.or(other.first_invalid),
+            encoding: match (self.encoding, other.encoding) {
+                (Some(a), Some(b)) if a != b => None,
+                (a, b) => a.or(b),
+            },
         }
     }
 }
@@ -29,67 +113,536 @@ impl std::ops::AddAssign for Counts {
     }
 }
 
-fn read_contents(input: &mut impl Read) -> Result<String> {
-    let mut content = String::new();
-    let mut reader = BufReader::new(input);
-    reader.read_to_string(&mut content)?;
-    Ok(content)
+/// Options for a counting pass
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Distance between tab stops when measuring line widths
+    pub tab_width: usize,
+    /// What separates one word from the next
+    pub word_boundary: WordBoundary,
+    /// Decode the input as UTF-8 to count characters, grapheme clusters,
+    /// line widths, and invalid sequences.
+    /// Without it, only bytes, lines, and words are counted, which is a lot
+    /// faster. Non-ASCII word boundaries always decode the input.
+    /// Off by default.
+    pub decode: bool,
+    /// Count sentences and paragraphs, which takes another look at every
+    /// character of the decoded input. Off by default.
+    pub prose: bool,
+    /// Encoding of the input, or `None` to detect it from its byte order
+    /// mark and fall back to UTF-8
+    pub encoding: Option<Encoding>,
 }
 
-/// Count words, lines, and characters
-/// Optimized for performance
-fn count_all(mut input: impl Read) -> Result<Counts> {
-    let mut counts = Counts {
-        words: 0,
-        lines: 0,
-        characters: 0,
-    };
-    let mut in_word = false;
-    let mut buf = [0u8; 4096];
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tab_width: 8,
+            word_boundary: WordBoundary::default(),
+            decode: false,
+            prose: false,
+            encoding: None,
+        }
+    }
+}
 
-    loop {
-        let bytes_read = input.read(&mut buf)?;
-        if bytes_read == 0 {
-            break;
+/// Size of the buffer the input is streamed through
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Stands in for invalid UTF-8 sequences
+const REPLACEMENT: &str = "\u{FFFD}";
+
+/// State of a single streaming counting pass
+struct Counter<'a, 'm> {
+    options: Options,
+    counts: Counts,
+    /// Known once the start of the input was checked for a byte order mark
+    encoding: Option<Encoding>,
+    bytes: Bytes,
+    lines: Lines,
+    words: Words,
+    chars: Chars,
+    graphemes: Graphemes,
+    max_line_width: MaxLineWidth,
+    sentences: Sentences,
+    paragraphs: Paragraphs,
+    /// Custom metrics counted alongside the built-in ones
+    custom: &'a mut [&'m mut dyn Metric],
+    /// Reused buffer for decoded text
+    text: String,
+}
+
+impl<'a, 'm> Counter<'a, 'm> {
+    fn new(options: &Options, custom: &'a mut [&'m mut dyn Metric]) -> Self {
+        Self {
+            options: options.clone(),
+            counts: Counts::default(),
+            encoding: None,
+            bytes: Bytes::default(),
+            lines: Lines::default(),
+            words: Words::new(options.word_boundary),
+            chars: Chars::default(),
+            graphemes: Graphemes::default(),
+            max_line_width: MaxLineWidth::new(options.tab_width),
+            sentences: Sentences::default(),
+            paragraphs: Paragraphs::default(),
+            custom,
+            text: String::new(),
         }
+    }
 
-        for &byte in &buf[..bytes_read] {
-            counts.characters += 1;
-            match byte {
-                b'\n' => {
-                    counts.lines += 1;
-                    in_word = false;
-                }
-                _ if byte.is_ascii_whitespace() => in_word = false,
-                _ => {
-                    if !in_word {
-                        counts.words += 1;
-                        in_word = true;
-                    }
-                }
+    /// Whether the input needs to be decoded at all
+    fn needs_text(&self) -> bool {
+        self.options.decode
+            || self.options.prose
+            || self.words.needs_text()
+            || self.custom.iter().any(|metric| metric.needs_text())
+    }
+
+    /// Count the next chunk of raw input and return how many bytes of it
+    /// were used.
+    ///
+    /// A multi-byte sequence cut off at the end of the chunk is left for the
+    /// next call, unless this is the end of the input.
+    fn update(&mut self, bytes: &[u8], eof: bool) -> usize {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            // Wait for enough input to look for a byte order mark
+            None if bytes.len() < MAX_BOM_LEN && !eof => return 0,
+            None => {
+                let (encoding, bom) = Encoding::detect(bytes, self.options.encoding);
+                self.encoding = Some(encoding);
+                self.counts.encoding = Some(encoding);
+                self.bytes.update(&Chunk {
+                    raw: &bytes[..bom],
+                    bytes: &[],
+                    text: None,
+                });
+                return bom + self.update(&bytes[bom..], eof);
             }
+        };
+
+        let ascii_compatible = encoding.is_ascii_compatible();
+        let mut decoded = std::mem::take(&mut self.text);
+        decoded.clear();
+        let (consumed, text) = if !self.needs_text() && ascii_compatible {
+            (bytes.len(), None)
+        } else if let (Encoding::Utf8, Ok(text)) = (encoding, std::str::from_utf8(bytes)) {
+            // Fast path: valid UTF-8 needs no copy
+            (bytes.len(), Some(text))
+        } else {
+            let offset = self.bytes.count();
+            let counts = &mut self.counts;
+            let consumed = encoding.decode(bytes, eof, |piece| match piece {
+                Decoded::Text(text) => decoded.push_str(text),
+                Decoded::Invalid(invalid) => {
+                    counts.invalid_sequences += 1;
+                    counts.first_invalid.get_or_insert(offset + invalid);
+                    decoded.push_str(REPLACEMENT);
+                }
+            });
+            (consumed, Some(decoded.as_str()))
+        };
+
+        // In ASCII-compatible encodings, newlines and ASCII whitespace can
+        // be found in the raw bytes directly
+        let raw = &bytes[..consumed];
+        let chunk = Chunk {
+            raw,
+            bytes: if ascii_compatible {
+                raw
+            } else {
+                text.unwrap_or_default().as_bytes()
+            },
+            text,
+        };
+        self.bytes.update(&chunk);
+        match self.words.update_counting_lines(&chunk) {
+            Some(newlines) => self.lines.update_counted(&chunk, newlines),
+            None => self.lines.update(&chunk),
+        }
+        self.chars.update(&chunk);
+        self.graphemes.update(&chunk);
+        self.max_line_width.update(&chunk);
+        if self.options.prose {
+            self.sentences.update(&chunk);
+            self.paragraphs.update(&chunk);
+        }
+        for metric in self.custom.iter_mut() {
+            metric.update(&chunk);
         }
+
+        self.text = decoded;
+        consumed
     }
 
-    Ok(counts)
+    fn finish(mut self) -> Counts {
+        self.lines.finish();
+        self.words.finish();
+        self.graphemes.finish();
+        self.max_line_width.finish();
+        self.sentences.finish();
+        self.paragraphs.finish();
+        for metric in self.custom.iter_mut() {
+            metric.finish();
+        }
+        Counts {
+            bytes: self.bytes.count(),
+            lines: self.lines.count(),
+            sentences: self.sentences.count(),
+            paragraphs: self.paragraphs.count(),
+            words: self.words.count(),
+            chars: self.chars.count(),
+            graphemes: self.graphemes.count(),
+            max_line_width: self.max_line_width.width(),
+            longest_line: self.max_line_width.line(),
+            ..self.counts
+        }
+    }
 }
 
 /// Count words, lines, and characters
+/// Optimized for performance
+///
+/// The input is streamed through a fixed-size buffer, so memory usage is
+/// constant no matter how large the input is.
+fn count_all(
+    mut input: impl Read,
+    options: &Options,
+    metrics: &mut [&mut dyn Metric],
+) -> Result<Counts> {
+    let mut counter = Counter::new(options, metrics);
+    let mut buf = vec![0u8; BUFFER_SIZE];
+    // Bytes of a UTF-8 sequence cut off by the previous read
+    let mut pending = 0;
+
+    loop {
+        let bytes_read = match input.read(&mut buf[pending..]) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        };
+        let filled = pending + bytes_read;
+        let consumed = counter.update(&buf[..filled], false);
+        buf.copy_within(consumed..filled, 0);
+        pending = filled - consumed;
+    }
+    counter.update(&buf[..pending], true);
+
+    Ok(counter.finish())
+}
+
+/// Count words, lines, and bytes, like `wc` without any flags
+///
+/// Never buffers the whole input, so it works on huge files and
+/// never-ending streams like `/dev/zero` alike. Characters, sentences, and
+/// the rest are counted with [`Options`] that turn them on.
 pub fn counter(input: &mut impl Read) -> Result<Counts> {
-    let contents = read_contents(input)?;
-    Ok(count_all(contents.as_bytes())?)
+    counter_with(input, &Options::default())
+}
+
+/// Count words, lines, and characters with custom [`Options`]
+pub fn counter_with(input: &mut impl Read, options: &Options) -> Result<Counts> {
+    count_all(input, options, &mut [])
+}
+
+/// Count custom [`Metric`]s in the same pass as the built-in counts.
+///
+/// Each metric sees every chunk of the input and is finished at the end,
+/// so its results can be read once this returns.
+pub fn counter_with_metrics(
+    input: &mut impl Read,
+    options: &Options,
+    metrics: &mut [&mut dyn Metric],
+) -> Result<Counts> {
+    count_all(input, options, metrics)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::io::Read;
+
+    use crate::testing::Trickle;
+
+    /// Options that count characters, too
+    fn decoding() -> super::Options {
+        super::Options {
+            decode: true,
+            ..Default::default()
+        }
+    }
 
     #[test]
     fn test_count_with_reader() {
         let input = "Hello, World!";
         let mut reader = input.as_bytes();
-        let counts = super::counter(&mut reader).unwrap();
+        let counts = super::counter_with(&mut reader, &decoding()).unwrap();
         assert_eq!(counts.words, 2);
         assert_eq!(counts.lines, 1);
-        assert_eq!(counts.characters, 13);
+        assert_eq!(counts.bytes, 13);
+        assert_eq!(counts.chars, 13);
+        assert_eq!(counts.graphemes, 13);
+    }
+
+    #[test]
+    fn test_count_across_reads() {
+        let mut reader = Trickle(b"Hello, World!\nThis is a test.");
+        let counts = super::counter(&mut reader).unwrap();
+        assert_eq!(counts.words, 6);
+        assert_eq!(counts.lines, 2);
+        assert_eq!(counts.bytes, 29);
+    }
+
+    #[test]
+    fn test_count_large_stream() {
+        // Larger than any internal buffer; never materialized in memory
+        let len = 16 * 1024 * 1024;
+        let mut reader = std::io::repeat(b'a').take(len);
+        let counts = super::counter_with(&mut reader, &decoding()).unwrap();
+        assert_eq!(counts.words, 1);
+        assert_eq!(counts.lines, 1);
+        assert_eq!(counts.bytes, 16 * 1024 * 1024);
+        assert_eq!(counts.graphemes, 16 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_count_bytes_chars_graphemes() {
+        // "é" is written as "e" + combining acute accent; the flag is two
+        // regional indicators; "\r\n" is a single grapheme cluster
+        let input = "naïve cafe\u{301} 🇩🇪\r\n日本語";
+        for counts in [
+            super::counter_with(&mut input.as_bytes(), &decoding()).unwrap(),
+            super::counter_with(&mut Trickle(input.as_bytes()), &decoding()).unwrap(),
+        ] {
+            assert_eq!(counts.bytes, input.len());
+            assert_eq!(counts.bytes, 33);
+            assert_eq!(counts.chars, 19);
+            assert_eq!(counts.graphemes, 16);
+            assert_eq!(counts.words, 4);
+            assert_eq!(counts.lines, 2);
+        }
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        // Latin-1 "é", a stray continuation byte, and a truncated "é" at the end
+        let input = b"caf\xe9 au\x80 lait\xc3";
+        for counts in [
+            super::counter_with(&mut &input[..], &decoding()).unwrap(),
+            super::counter_with(&mut Trickle(input), &decoding()).unwrap(),
+        ] {
+            assert_eq!(counts.bytes, 14);
+            assert_eq!(counts.words, 3);
+            assert_eq!(counts.lines, 1);
+            assert_eq!(counts.chars, 14);
+            assert_eq!(counts.invalid_sequences, 3);
+            assert_eq!(counts.first_invalid, Some(3));
+        }
+
+        let counts = super::counter_with(&mut "café".as_bytes(), &decoding()).unwrap();
+        assert_eq!(counts.invalid_sequences, 0);
+        assert_eq!(counts.first_invalid, None);
+    }
+
+    #[test]
+    fn test_without_decoding() {
+        // Nothing is decoded unless asked for
+        let counts = super::counter(&mut &b"caf\xe9 au lait. Bye!\n"[..]).unwrap();
+        assert_eq!(counts.bytes, 19);
+        assert_eq!(counts.words, 4);
+        assert_eq!(counts.lines, 1);
+        assert_eq!(counts.chars, 0);
+        assert_eq!(counts.invalid_sequences, 0);
+        assert_eq!(counts.sentences, 0);
+    }
+
+    #[test]
+    fn test_max_line_width() {
+        // Tabs expand to the next tab stop, "e" + U+0301 takes up a single
+        // column and each CJK character takes up two
+        let input = "short\n\tcafe\u{301}\n日本語\r\nlonger line\n";
+        let counts = super::counter_with(&mut input.as_bytes(), &decoding()).unwrap();
+        assert_eq!(counts.max_line_width, 12);
+        assert_eq!(counts.longest_line, 2);
+
+        let options = super::Options {
+            tab_width: 4,
+            ..decoding()
+        };
+        let counts = super::counter_with(&mut input.as_bytes(), &options).unwrap();
+        assert_eq!(counts.max_line_width, 11);
+        assert_eq!(counts.longest_line, 4);
+    }
+
+    #[test]
+    fn test_max_line_width_without_trailing_newline() {
+        let counts = super::counter_with(&mut &b"a\nlast line"[..], &decoding()).unwrap();
+        assert_eq!(counts.max_line_width, 9);
+        assert_eq!(counts.longest_line, 2);
+
+        let counts = super::counter_with(&mut &b""[..], &decoding()).unwrap();
+        assert_eq!(counts.max_line_width, 0);
+        assert_eq!(counts.longest_line, 0);
+    }
+
+    #[test]
+    fn test_word_boundaries() {
+        use super::WordBoundary;
+
+        // No-break space, ideographic space, and an apostrophe
+        let input = "one\u{a0}two\u{3000}three can't stop, 日本語";
+        let count = |word_boundary| {
+            let options = super::Options {
+                word_boundary,
+                ..Default::default()
+            };
+            let words = super::counter_with(&mut input.as_bytes(), &options)
+                .unwrap()
+                .words;
+            let trickled = super::counter_with(&mut Trickle(input.as_bytes()), &options)
+                .unwrap()
+                .words;
+            assert_eq!(words, trickled, "{word_boundary:?}");
+            words
+        };
+        assert_eq!(count(WordBoundary::Ascii), 4);
+        assert_eq!(count(WordBoundary::Unicode), 6);
+        assert_eq!(count(WordBoundary::Uax29), 8);
+    }
+
+    #[test]
+    fn test_utf16() {
+        use super::Encoding;
+
+        let text = "Grüße, 世界 🦀\r\nzwei Zeilen\n";
+        let le: Vec<u8> = b"\xFF\xFE"
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let be: Vec<u8> = b"\xFE\xFF"
+            .iter()
+            .copied()
+            .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+
+        for (input, encoding) in [(&le, Encoding::Utf16Le), (&be, Encoding::Utf16Be)] {
+            for counts in [
+                super::counter_with(&mut input.as_slice(), &decoding()).unwrap(),
+                super::counter_with(&mut Trickle(input), &decoding()).unwrap(),
+            ] {
+                assert_eq!(counts.encoding, Some(encoding));
+                assert_eq!(counts.bytes, input.len());
+                assert_eq!(counts.chars, text.chars().count());
+                assert_eq!(counts.words, 5);
+                assert_eq!(counts.lines, 2);
+                assert_eq!(counts.max_line_width, 14);
+                assert_eq!(counts.invalid_sequences, 0);
+            }
+        }
+
+        // Without a BOM, the encoding has to be given explicitly
+        let options = super::Options {
+            encoding: Some(Encoding::Utf16Le),
+            ..decoding()
+        };
+        let counts = super::counter_with(&mut &le[2..], &options).unwrap();
+        assert_eq!(counts.encoding, Some(Encoding::Utf16Le));
+        assert_eq!(counts.chars, text.chars().count());
+    }
+
+    #[test]
+    fn test_utf16_unpaired_surrogates() {
+        // "a", a lone low surrogate, "b", a lone high surrogate at the end
+        let input = b"\xFF\xFEa\x00\x00\xDCb\x00\x00\xD8";
+        let counts = super::counter_with(&mut &input[..], &decoding()).unwrap();
+        assert_eq!(counts.chars, 4);
+        assert_eq!(counts.words, 1);
+        assert_eq!(counts.invalid_sequences, 2);
+        assert_eq!(counts.first_invalid, Some(4));
+    }
+
+    #[test]
+    fn test_utf8_bom() {
+        let counts = super::counter_with(&mut "\u{FEFF} hi".as_bytes(), &decoding()).unwrap();
+        assert_eq!(counts.encoding, Some(super::Encoding::Utf8));
+        assert_eq!(counts.bytes, 6);
+        assert_eq!(counts.chars, 3);
+        assert_eq!(counts.words, 1);
+    }
+
+    #[test]
+    fn test_single_byte_encodings() {
+        use super::{Encoding, WordBoundary};
+
+        // "“Grüße”\xA0aus Köln" with curly quotes and a no-break space
+        let input = b"\x93Gr\xFC\xDFe\x94\xA0aus K\xF6ln\n";
+        let count = |encoding, word_boundary| {
+            let options = super::Options {
+                encoding: Some(encoding),
+                word_boundary,
+                ..decoding()
+            };
+            super::counter_with(&mut &input[..], &options).unwrap()
+        };
+
+        let counts = count(Encoding::Windows1252, WordBoundary::Unicode);
+        assert_eq!(counts.encoding, Some(Encoding::Windows1252));
+        assert_eq!(counts.bytes, 17);
+        assert_eq!(counts.chars, 17);
+        assert_eq!(counts.words, 3);
+        assert_eq!(counts.lines, 1);
+        assert_eq!(counts.max_line_width, 16);
+        assert_eq!(counts.invalid_sequences, 0);
+
+        // ASCII whitespace doesn't split at the no-break space
+        assert_eq!(count(Encoding::Windows1252, WordBoundary::Ascii).words, 2);
+
+        // In Latin-1, 0x93 and 0x94 are invisible control codes
+        let counts = count(Encoding::Latin1, WordBoundary::Unicode);
+        assert_eq!(counts.chars, 17);
+        assert_eq!(counts.max_line_width, 14);
+    }
+
+    /// Counts question marks, like a team-specific metric would
+    #[derive(Default)]
+    struct Questions {
+        count: usize,
+        finished: bool,
+    }
+
+    impl super::Metric for Questions {
+        fn update(&mut self, chunk: &super::metric::Chunk<'_>) {
+            self.count += chunk.text.unwrap_or_default().matches('?').count();
+        }
+
+        fn finish(&mut self) {
+            self.finished = true;
+        }
+
+        fn merge(&mut self, next: Self) {
+            self.count += next.count;
+        }
+    }
+
+    #[test]
+    fn test_custom_metric() {
+        let mut questions = Questions::default();
+        let options = super::Options {
+            decode: false,
+            ..Default::default()
+        };
+        let input = "Why? Who?\nWhere? ¿Qué?\n".repeat(1_000);
+        let counts = super::counter_with_metrics(
+            &mut Trickle(input.as_bytes()),
+            &options,
+            &mut [&mut questions],
+        )
+        .unwrap();
+        assert_eq!(counts.words, 4_000);
+        // The input is decoded for the custom metric alone
+        assert_eq!(questions.count, 4_000);
+        assert!(questions.finished);
     }
 }
@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+
+use crate::metric::{Chunk, Metric};
+use crate::{WordBoundary, Words};
+
+/// Distribution of a count over all lines, e.g. of their lengths
+///
+/// Keeps how often each value occurs, so memory grows with the number of
+/// distinct values rather than with the number of lines.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Distribution {
+    counts: BTreeMap<usize, usize>,
+    len: usize,
+    sum: usize,
+}
+
+impl Distribution {
+    pub fn add(&mut self, value: usize) {
+        *self.counts.entry(value).or_default() += 1;
+        self.len += 1;
+        self.sum += value;
+    }
+
+    /// Number of values, i.e. lines
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn min(&self) -> Option<usize> {
+        self.counts.keys().next().copied()
+    }
+
+    pub fn max(&self) -> Option<usize> {
+        self.counts.keys().next_back().copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        (self.len > 0).then(|| self.sum as f64 / self.len as f64)
+    }
+
+    /// The value below or at which a fraction `q` of all values lie, from
+    /// 0 to 1, using the nearest-rank method.
+    /// Unlike an interpolated quantile, it's always one of the values.
+    pub fn quantile(&self, q: f64) -> Option<usize> {
+        let rank = ((q * self.len as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+        self.counts.iter().find_map(|(&value, &count)| {
+            seen += count;
+            (seen >= rank).then_some(value)
+        })
+    }
+
+    pub fn median(&self) -> Option<usize> {
+        self.quantile(0.5)
+    }
+
+    /// Number of values in each bucket of `width` values, starting at 0,
+    /// as `(first value, last value, count)`.
+    /// Empty buckets are left out, so a single outlier doesn't add a bucket
+    /// for every width between it and the rest.
+    ///
+    /// # Panics
+    ///
+    /// If `width` is 0
+    pub fn histogram(&self, width: usize) -> Vec<(usize, usize, usize)> {
+        assert!(width > 0, "buckets need to be at least one value wide");
+        let mut buckets: Vec<(usize, usize, usize)> = Vec::new();
+        for (&value, &count) in &self.counts {
+            let first = value - value % width;
+            match buckets.last_mut() {
+                Some(bucket) if bucket.0 == first => bucket.2 += count,
+                _ => buckets.push((first, first.saturating_add(width - 1), count)),
+            }
+        }
+        buckets
+    }
+}
+
+/// Add up the distributions of separate inputs, e.g. for a total over
+/// several files
+impl std::ops::AddAssign<&Self> for Distribution {
+    fn add_assign(&mut self, other: &Self) {
+        for (&value, &count) in &other.counts {
+            *self.counts.entry(value).or_default() += count;
+        }
+        self.len += other.len;
+        self.sum += other.sum;
+    }
+}
+
+/// The part of a line seen so far
+#[derive(Debug, Clone)]
+struct Line {
+    chars: usize,
+    words: Words,
+    /// Whether the last character is a carriage return, which belongs to
+    /// the line break if a newline follows
+    ends_with_cr: bool,
+}
+
+impl Line {
+    fn new(boundary: WordBoundary) -> Self {
+        Self {
+            chars: 0,
+            words: Words::new(boundary),
+            ends_with_cr: false,
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        self.chars += text.chars().count();
+        self.words.update(&Chunk::from_text(text));
+        self.ends_with_cr = text.ends_with('\r');
+    }
+
+    /// Continue with the line as seen by the text that directly follows
+    fn append(&mut self, next: Self) {
+        if next.chars == 0 {
+            return;
+        }
+        self.chars += next.chars;
+        self.words.merge(next.words);
+        self.ends_with_cr = next.ends_with_cr;
+    }
+
+    /// Length and number of words of the line, once it has ended
+    fn end(mut self) -> (usize, usize) {
+        self.words.finish();
+        (
+            self.chars - usize::from(self.ends_with_cr),
+            self.words.count(),
+        )
+    }
+}
+
+/// Distributions of the length of lines and the number of words on them
+///
+/// Lengths are in characters, not counting the line break, be it `\n`
+/// or `\r\n`. Words are told apart like [`Words`] does. Like for
+/// [`Counts::lines`](crate::Counts::lines), a last line without a line
+/// break counts as long as it isn't empty.
+#[derive(Debug, Clone)]
+pub struct LineStats {
+    lengths: Distribution,
+    words: Distribution,
+    /// A line without text before it, to start the next one from
+    empty: Line,
+    /// Text before the first line break, which might continue a line from
+    /// the text before
+    head: Line,
+    /// Whether there was a line break yet, so `tail` comes after `head`
+    split: bool,
+    /// Text after the last line break
+    tail: Line,
+}
+
+impl LineStats {
+    pub fn new(boundary: WordBoundary) -> Self {
+        let empty = Line::new(boundary);
+        Self {
+            lengths: Distribution::default(),
+            words: Distribution::default(),
+            head: empty.clone(),
+            split: false,
+            tail: empty.clone(),
+            empty,
+        }
+    }
+
+    /// Lengths of the lines, in characters
+    pub const fn lengths(&self) -> &Distribution {
+        &self.lengths
+    }
+
+    /// Number of words on each line
+    pub const fn words(&self) -> &Distribution {
+        &self.words
+    }
+
+    fn add(&mut self, line: Line) {
+        let (length, words) = line.end();
+        self.lengths.add(length);
+        self.words.add(words);
+    }
+
+    /// The current line has ended
+    fn end_line(&mut self) {
+        let line = std::mem::replace(&mut self.tail, self.empty.clone());
+        self.add(line);
+    }
+}
+
+impl Metric for LineStats {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        let mut lines = text.split('\n');
+        let first = lines.next().unwrap_or_default();
+        let Some(last) = lines.next_back() else {
+            // The current line goes on
+            if self.split {
+                self.tail.push(first);
+            } else {
+                self.head.push(first);
+            }
+            return;
+        };
+        if self.split {
+            self.tail.push(first);
+            self.end_line();
+        } else {
+            self.head.push(first);
+            self.split = true;
+        }
+        for line in lines {
+            self.tail.push(line);
+            self.end_line();
+        }
+        self.tail.push(last);
+    }
+
+    fn finish(&mut self) {
+        let head = std::mem::replace(&mut self.head, self.empty.clone());
+        if self.split || head.chars > 0 {
+            self.add(head);
+        }
+        if self.tail.chars > 0 {
+            self.end_line();
+        }
+        self.split = true;
+    }
+
+    fn merge(&mut self, next: Self) {
+        if !next.split {
+            if self.split {
+                self.tail.append(next.head);
+            } else {
+                self.head.append(next.head);
+            }
+            return;
+        }
+        if self.split {
+            self.tail.append(next.head);
+            self.end_line();
+        } else {
+            self.head.append(next.head);
+            self.split = true;
+        }
+        self.lengths += &next.lengths;
+        self.words += &next.words;
+        self.tail = next.tail;
+    }
+}
+
+/// Add up the distributions of separate inputs, e.g. for a total over
+/// several files
+impl std::ops::AddAssign for LineStats {
+    fn add_assign(&mut self, other: Self) {
+        self.lengths += &other.lengths;
+        self.words += &other.words;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(pieces: &[&str], boundary: WordBoundary) -> LineStats {
+        let mut merged = LineStats::new(boundary);
+        for piece in pieces {
+            let mut stats = LineStats::new(boundary);
+            stats.update(&Chunk::from_text(piece));
+            merged.merge(stats);
+        }
+        merged.finish();
+        merged
+    }
+
+    #[test]
+    fn test_distribution() {
+        let mut distribution = Distribution::default();
+        assert_eq!(distribution.median(), None);
+        assert_eq!(distribution.mean(), None);
+        assert_eq!(distribution.histogram(10), []);
+        for value in [5, 1, 3, 3, 100, 2, 4, 3, 2, 1] {
+            distribution.add(value);
+        }
+        assert_eq!(distribution.len(), 10);
+        assert_eq!(distribution.min(), Some(1));
+        assert_eq!(distribution.max(), Some(100));
+        assert_eq!(distribution.mean(), Some(12.4));
+        assert_eq!(distribution.median(), Some(3));
+        assert_eq!(distribution.quantile(0.95), Some(100));
+        assert_eq!(distribution.quantile(0.9), Some(5));
+        assert_eq!(distribution.quantile(0.0), Some(1));
+        assert_eq!(distribution.histogram(40), [(0, 39, 9), (80, 119, 1)]);
+    }
+
+    #[test]
+    fn test_lines() {
+        let stats = count(&["one two\r\n\nthree  four five\nsix"], WordBoundary::Ascii);
+        let lengths = stats.lengths();
+        assert_eq!(lengths.len(), 4);
+        assert_eq!((lengths.min(), lengths.max()), (Some(0), Some(16)));
+        assert_eq!(lengths.mean(), Some(26.0 / 4.0));
+        let words = stats.words();
+        assert_eq!(
+            words.histogram(1),
+            [(0, 0, 1), (1, 1, 1), (2, 2, 1), (3, 3, 1)]
+        );
+
+        // One very long line doesn't fill the gap with empty buckets
+        let long = format!("a b\n{}\n", "x".repeat(3_000_000));
+        let stats = count(&[&long], WordBoundary::Ascii);
+        assert_eq!(
+            stats.lengths().histogram(1),
+            [(3, 3, 1), (3_000_000, 3_000_000, 1)]
+        );
+        assert_eq!(stats.words().histogram(1), [(1, 1, 1), (2, 2, 1)]);
+
+        // No line at all, or only a line break
+        assert!(count(&[""], WordBoundary::Ascii).lengths().is_empty());
+        assert_eq!(count(&["\n"], WordBoundary::Ascii).lengths().len(), 1);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "naïve don't\r\n\nstop,\u{3000}now\n\n";
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for boundary in [
+            WordBoundary::Ascii,
+            WordBoundary::Unicode,
+            WordBoundary::Uax29,
+        ] {
+            let expected = count(&[text], boundary);
+            for &first in &boundaries {
+                for &second in boundaries.iter().filter(|&&i| i >= first) {
+                    let stats = count(
+                        &[&text[..first], &text[first..second], &text[second..]],
+                        boundary,
+                    );
+                    assert_eq!(
+                        (stats.lengths(), stats.words()),
+                        (expected.lengths(), expected.words()),
+                        "{boundary:?} split at {first} and {second}"
+                    );
+                }
+            }
+        }
+    }
+}
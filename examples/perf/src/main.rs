@@ -4,46 +4,1365 @@
 #![warn(clippy::nursery)]
 #![warn(clippy::cargo)]
 
-use wc::{counter, Counts};
+use wc::{
+    counter_with_metrics, CharClass, CharClasses, CodeBlocks, CodeLines, Column, Counts, Cue,
+    CueTotals, DelimitedText, Distribution, Elements, Frequencies, Headings, JsonPath, JsonStrings,
+    JsonText, Language, Latex, LineStats, Markdown, Markup, MarkupText, Metric, NGrams, Options,
+    Readability, Records, Scores, Section, SectionSplitter, SourceLines, SubtitleText, Subtitles,
+    TexCounts, TexInput, Vocabulary,
+};
 
-use std::{fs::File, io::Read};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    fmt::Write,
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+    time::Duration,
+};
 
 const STDIN: &str = "-";
 
+/// Reading speed above which a subtitle cue is too fast, unless it's
+/// given with `--max-wpm`
+const DEFAULT_MAX_WPM: usize = 180;
+
+const USAGE: &str =
+    "Usage: wc [-lspwmgcL] [--tab-width=N] [--words=ascii|unicode|uax29] [--invalid]\n          [--encoding=NAME] [--show-encoding] [--top=N] [--ngrams=N]\n          [--min-count=N] [--ignore-case] [--readability] [--vocabulary]\n          [--classes] [--code-points] [--code]\n          [--markdown] [--code-blocks] [--sections] [--html|--xml] [--elements]\n          [--latex] [--subtitles] [--cues] [--max-wpm=N]\n          [--delimited=csv|tsv|C] [--header] [--select=COLUMNS]\n          [--json-strings] [--json-path=PATH] [--by-heading[=REGEX]]\n          [--line-stats] [--histogram=N]\n          [--json|--csv] <path>...";
+
 /// Custom error type
 type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
 
-fn main() -> Result<()> {
-    if std::env::args().len() < 2 {
-        eprintln!("Usage: wc <path>");
-        return Err("Could not read file".into());
+/// Which counts to print
+///
+/// Like `wc`, the columns are always printed in the same order,
+/// no matter in which order the flags were given.
+#[derive(Debug, Default, PartialEq, Eq)]
+#[allow(clippy::struct_excessive_bools)]
+struct Columns {
+    lines: bool,
+    sentences: bool,
+    paragraphs: bool,
+    words: bool,
+    chars: bool,
+    graphemes: bool,
+    bytes: bool,
+    max_line_width: bool,
+    invalid: bool,
+    encoding: bool,
+    readability: bool,
+    vocabulary: bool,
+    classes: bool,
+    line_stats: bool,
+    code: bool,
+    code_blocks: bool,
+    latex: bool,
+    subtitles: bool,
+    records: bool,
+    json_strings: bool,
+}
+
+/// Everything to print about a single input or the total
+struct Report<'a> {
+    name: &'a str,
+    counts: Counts,
+    scores: Option<Scores>,
+    vocabulary: Option<Vocabulary>,
+    classes: Option<&'a CharClasses>,
+    line_stats: Option<&'a LineStats>,
+    /// Language of the source code, if it's the same for all of the input
+    language: Option<Language>,
+    source_lines: Option<SourceLines>,
+    code_blocks: Option<CodeBlocks>,
+    /// Words and formulas of LaTeX source, by what they are part of
+    tex: Option<TexCounts>,
+    cue_totals: Option<CueTotals>,
+    /// Cues of subtitles, and whether each is read too fast, if they are
+    /// printed
+    cues: Option<Vec<(Cue, bool)>>,
+    /// Records of delimited data, if it's counted
+    records: Option<&'a Records>,
+    /// Strings of JSON, if they are counted
+    json_strings: Option<&'a JsonStrings>,
+    /// Sections of Markdown text under each heading, if they are printed
+    sections: Option<Vec<&'a Section>>,
+    /// Counts of each element of HTML or XML markup, if they are printed
+    elements: Option<&'a Elements>,
+    /// Width of the buckets of line histograms, if they are printed
+    histogram: Option<usize>,
+    /// Listed words, n-grams, or code points with their counts
+    entries: Option<Vec<(Cow<'a, str>, usize)>>,
+}
+
+/// Buckets of a histogram, as `(first value, last value, count)`
+type Buckets = Vec<(usize, usize, usize)>;
+
+impl<'a> Report<'a> {
+    /// A report with the plain counts only
+    const fn new(name: &'a str, counts: Counts) -> Self {
+        Self {
+            name,
+            counts,
+            scores: None,
+            vocabulary: None,
+            classes: None,
+            line_stats: None,
+            language: None,
+            source_lines: None,
+            code_blocks: None,
+            tex: None,
+            cue_totals: None,
+            cues: None,
+            records: None,
+            json_strings: None,
+            sections: None,
+            elements: None,
+            histogram: None,
+            entries: None,
+        }
+    }
+
+    /// Histograms of the line lengths and words per line, and of the
+    /// words per record of JSON, if they are printed
+    fn histograms(&self) -> Vec<(&'static str, Buckets)> {
+        let Some(width) = self.histogram else {
+            return Vec::new();
+        };
+        let mut histograms = Vec::new();
+        if let Some(stats) = self.line_stats {
+            histograms.push(("line_length", stats.lengths().histogram(width)));
+            histograms.push(("words_per_line", stats.words().histogram(width)));
+        }
+        if let Some(json) = self.json_strings {
+            histograms.push(("words_per_record", json.words().histogram(width)));
+        }
+        histograms
+    }
+}
+
+/// Value of a column, or `None` if there's nothing to show
+enum Value {
+    Number(Option<String>),
+    Text(Option<String>),
+}
+
+impl Value {
+    fn count(count: usize) -> Self {
+        Self::Number(Some(count.to_string()))
+    }
+
+    fn decimal(value: Option<f64>, precision: usize) -> Self {
+        Self::Number(value.map(|value| format!("{value:.precision$}")))
+    }
+
+    /// Scores are rounded to one decimal, like most readability tools do
+    fn score(scores: Option<&Scores>, score: impl Fn(&Scores) -> f64) -> Self {
+        Self::decimal(scores.map(score), 1)
+    }
+
+    fn to_text(&self) -> &str {
+        match self {
+            Self::Number(value) | Self::Text(value) => value.as_deref().unwrap_or("-"),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        match self {
+            Self::Number(Some(number)) => number.clone(),
+            Self::Text(Some(text)) => json_string(text),
+            Self::Number(None) | Self::Text(None) => "null".to_string(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        match self {
+            Self::Number(value) => value.clone().unwrap_or_default(),
+            Self::Text(value) => value.as_deref().map(csv_field).unwrap_or_default(),
+        }
+    }
+}
+
+/// Minimum, mean, median, 95th percentile, and maximum of a distribution
+fn summary(distribution: Option<&Distribution>) -> [Value; 5] {
+    let count = |value: Option<usize>| Value::Number(value.map(|value| value.to_string()));
+    [
+        count(distribution.and_then(Distribution::min)),
+        Value::decimal(distribution.and_then(Distribution::mean), 1),
+        count(distribution.and_then(Distribution::median)),
+        count(distribution.and_then(|distribution| distribution.quantile(0.95))),
+        count(distribution.and_then(Distribution::max)),
+    ]
+}
+
+/// Quote a CSV field if it contains commas, quotes, or line breaks
+/// (RFC 4180)
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Quote and escape `text` as a JSON string
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+    json.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", u32::from(c));
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+impl Columns {
+    /// Parse flags like `-l`, `-w` or combined ones like `-lw`
+    fn parse(&mut self, flags: &str) -> Result<()> {
+        for flag in flags.chars() {
+            match flag {
+                'l' => self.lines = true,
+                's' => self.sentences = true,
+                'p' => self.paragraphs = true,
+                'w' => self.words = true,
+                'm' => self.chars = true,
+                'g' => self.graphemes = true,
+                'c' => self.bytes = true,
+                'L' => self.max_line_width = true,
+                _ => return Err(format!("Unknown option: -{flag}\n{USAGE}").into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Without any flags, print lines, words, and bytes (same as `wc`)
+    fn or_default(self) -> Self {
+        if self == Self::default() {
+            Self {
+                lines: true,
+                words: true,
+                bytes: true,
+                ..Self::default()
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Only decode the input if a column needs characters
+    const fn needs_decoding(&self) -> bool {
+        self.chars || self.graphemes || self.max_line_width || self.invalid
+    }
+
+    /// Names and values of the columns to print
+    fn values(&self, report: &Report) -> Vec<(&'static str, Value)> {
+        let counts = &report.counts;
+        let classes = CharClass::ALL.iter().map(|&class| {
+            (
+                self.classes,
+                class.name(),
+                Value::Number(report.classes.map(|classes| classes.get(class).to_string())),
+            )
+        });
+        [
+            (self.lines, "lines", Value::count(counts.lines)),
+            (self.sentences, "sentences", Value::count(counts.sentences)),
+            (
+                self.paragraphs,
+                "paragraphs",
+                Value::count(counts.paragraphs),
+            ),
+            (self.words, "words", Value::count(counts.words)),
+            (self.chars, "chars", Value::count(counts.chars)),
+            (self.graphemes, "graphemes", Value::count(counts.graphemes)),
+            (self.bytes, "bytes", Value::count(counts.bytes)),
+            // The longest line is followed by its line number
+            (
+                self.max_line_width,
+                "max_line_width",
+                Value::count(counts.max_line_width),
+            ),
+            (
+                self.max_line_width,
+                "longest_line",
+                Value::count(counts.longest_line),
+            ),
+            // Invalid UTF-8 sequences are followed by the offset of the first one
+            (
+                self.invalid,
+                "invalid_sequences",
+                Value::count(counts.invalid_sequences),
+            ),
+            (
+                self.invalid,
+                "first_invalid",
+                Value::Number(counts.first_invalid.map(|offset| offset.to_string())),
+            ),
+            (
+                self.encoding,
+                "encoding",
+                Value::Text(counts.encoding.map(|encoding| encoding.to_string())),
+            ),
+        ]
+        .into_iter()
+        .chain(self.score_values(report.scores.as_ref()))
+        .chain(self.vocabulary_values(report.vocabulary.as_ref()))
+        .chain(self.line_values(report.line_stats))
+        .chain(self.code_values(report))
+        .chain(self.code_block_values(report.code_blocks))
+        .chain(self.latex_values(report.tex))
+        .chain(self.subtitle_values(report.cue_totals))
+        .chain(self.record_values(report.records))
+        .chain(self.json_values(report.json_strings))
+        .chain(classes)
+        .filter(|(enabled, _, _)| *enabled)
+        .map(|(_, name, value)| (name, value))
+        .collect()
+    }
+
+    /// Columns of the readability scores
+    fn score_values(&self, scores: Option<&Scores>) -> [(bool, &'static str, Value); 4] {
+        [
+            (
+                self.readability,
+                "flesch_reading_ease",
+                Value::score(scores, |scores| scores.flesch_reading_ease),
+            ),
+            (
+                self.readability,
+                "flesch_kincaid_grade",
+                Value::score(scores, |scores| scores.flesch_kincaid_grade),
+            ),
+            (
+                self.readability,
+                "gunning_fog",
+                Value::score(scores, |scores| scores.gunning_fog),
+            ),
+            (
+                self.readability,
+                "smog",
+                Value::score(scores, |scores| scores.smog),
+            ),
+        ]
+    }
+
+    /// Columns of the vocabulary statistics
+    fn vocabulary_values(
+        &self,
+        vocabulary: Option<&Vocabulary>,
+    ) -> [(bool, &'static str, Value); 5] {
+        [
+            (
+                self.vocabulary,
+                "distinct_words",
+                Value::Number(vocabulary.map(|vocabulary| vocabulary.distinct.to_string())),
+            ),
+            (
+                self.vocabulary,
+                "type_token_ratio",
+                Value::decimal(vocabulary.map(Vocabulary::type_token_ratio), 3),
+            ),
+            (
+                self.vocabulary,
+                "hapax_legomena",
+                Value::Number(vocabulary.map(|vocabulary| vocabulary.hapax_legomena.to_string())),
+            ),
+            (
+                self.vocabulary,
+                "average_word_length",
+                Value::decimal(
+                    vocabulary.map(|vocabulary| vocabulary.average_word_length),
+                    1,
+                ),
+            ),
+            (
+                self.vocabulary,
+                "max_word_length",
+                Value::Number(vocabulary.map(|vocabulary| vocabulary.max_word_length.to_string())),
+            ),
+        ]
+    }
+
+    /// Columns of the line length and words per line statistics
+    fn line_values(&self, stats: Option<&LineStats>) -> Vec<(bool, &'static str, Value)> {
+        let names = [
+            [
+                "line_length_min",
+                "line_length_mean",
+                "line_length_median",
+                "line_length_p95",
+                "line_length_max",
+            ],
+            [
+                "words_per_line_min",
+                "words_per_line_mean",
+                "words_per_line_median",
+                "words_per_line_p95",
+                "words_per_line_max",
+            ],
+        ];
+        let distributions = [stats.map(LineStats::lengths), stats.map(LineStats::words)];
+        names
+            .into_iter()
+            .zip(distributions)
+            .flat_map(|(names, distribution)| names.into_iter().zip(summary(distribution)))
+            .map(|(name, value)| (self.line_stats, name, value))
+            .collect()
+    }
+
+    /// Columns of the strings of JSON and the words per record
+    fn json_values(&self, json: Option<&JsonStrings>) -> Vec<(bool, &'static str, Value)> {
+        let names = [
+            "words_per_record_min",
+            "words_per_record_mean",
+            "words_per_record_median",
+            "words_per_record_p95",
+            "words_per_record_max",
+        ];
+        [
+            (
+                "records",
+                Value::Number(json.map(|json| json.records().to_string())),
+            ),
+            (
+                "strings",
+                Value::Number(json.map(|json| json.strings().to_string())),
+            ),
+        ]
+        .into_iter()
+        .chain(names.into_iter().zip(summary(json.map(JsonStrings::words))))
+        .map(|(name, value)| (self.json_strings, name, value))
+        .collect()
+    }
+
+    /// Columns of the lines of code, comments, and blank lines
+    fn code_values(&self, report: &Report) -> [(bool, &'static str, Value); 4] {
+        let count = |count: fn(SourceLines) -> usize| {
+            Value::Number(report.source_lines.map(|lines| count(lines).to_string()))
+        };
+        [
+            (
+                self.code,
+                "language",
+                Value::Text(report.language.map(|language| language.to_string())),
+            ),
+            (self.code, "code", count(|lines| lines.code)),
+            (self.code, "comments", count(|lines| lines.comments)),
+            (self.code, "blanks", count(|lines| lines.blanks)),
+        ]
+    }
+
+    /// Columns of the fenced code blocks of Markdown text
+    fn code_block_values(&self, blocks: Option<CodeBlocks>) -> [(bool, &'static str, Value); 3] {
+        let count = |count: fn(CodeBlocks) -> usize| {
+            Value::Number(blocks.map(|blocks| count(blocks).to_string()))
+        };
+        [
+            (
+                self.code_blocks,
+                "code_blocks",
+                count(|blocks| blocks.blocks),
+            ),
+            (self.code_blocks, "code_lines", count(|blocks| blocks.lines)),
+            (self.code_blocks, "code_words", count(|blocks| blocks.words)),
+        ]
+    }
+
+    /// Columns of the words and formulas of LaTeX source
+    fn latex_values(&self, tex: Option<TexCounts>) -> [(bool, &'static str, Value); 7] {
+        let count =
+            |count: fn(TexCounts) -> usize| Value::Number(tex.map(|tex| count(tex).to_string()));
+        [
+            (self.latex, "text_words", count(|tex| tex.text)),
+            (self.latex, "heading_words", count(|tex| tex.heading_words)),
+            (self.latex, "caption_words", count(|tex| tex.caption_words)),
+            (
+                self.latex,
+                "footnote_words",
+                count(|tex| tex.footnote_words),
+            ),
+            (self.latex, "headings", count(|tex| tex.headings)),
+            (self.latex, "inline_math", count(|tex| tex.inline_math)),
+            (self.latex, "display_math", count(|tex| tex.display_math)),
+        ]
+    }
+
+    /// Columns of the cues of subtitles
+    fn subtitle_values(&self, totals: Option<CueTotals>) -> [(bool, &'static str, Value); 4] {
+        [
+            (
+                self.subtitles,
+                "cues",
+                Value::Number(totals.map(|totals| totals.cues.to_string())),
+            ),
+            (
+                self.subtitles,
+                "duration",
+                Value::decimal(totals.map(|totals| totals.duration.as_secs_f64()), 3),
+            ),
+            (
+                self.subtitles,
+                "wpm",
+                Value::decimal(totals.and_then(|totals| totals.words_per_minute()), 1),
+            ),
+            (
+                self.subtitles,
+                "fast_cues",
+                Value::Number(totals.map(|totals| totals.fast.to_string())),
+            ),
+        ]
+    }
+
+    /// Columns of the records of delimited data
+    fn record_values(&self, records: Option<&Records>) -> [(bool, &'static str, Value); 3] {
+        [
+            (
+                self.records,
+                "records",
+                Value::Number(records.map(|records| records.len().to_string())),
+            ),
+            (
+                self.records,
+                "fields",
+                Value::Number(
+                    records
+                        .and_then(Records::fields)
+                        .map(|fields| fields.to_string()),
+                ),
+            ),
+            (
+                self.records,
+                "ragged",
+                Value::Number(records.map(|records| records.ragged_count().to_string())),
+            ),
+        ]
+    }
+
+    fn format(&self, report: &Report) -> String {
+        self.values(report)
+            .iter()
+            .map(|(_, value)| format!("{:>7} ", value.to_text()))
+            .chain(std::iter::once(report.name.to_string()))
+            .collect()
+    }
+
+    /// Format the columns as a JSON object on a single line, with the
+    /// listed words or n-grams if there are any
+    fn to_json(&self, report: &Report, kind: &str) -> String {
+        let mut fields = vec![format!("\"file\":{}", json_string(report.name))];
+        fields.extend(
+            self.values(report)
+                .iter()
+                .map(|(name, value)| format!("\"{name}\":{}", value.to_json())),
+        );
+        if let Some(top) = &report.entries {
+            let words: Vec<String> = top
+                .iter()
+                .map(|(word, count)| {
+                    format!("{{\"{kind}\":{},\"count\":{count}}}", json_string(word))
+                })
+                .collect();
+            fields.push(format!("\"top\":[{}]", words.join(",")));
+        }
+        for (name, buckets) in report.histograms() {
+            let buckets: Vec<String> = buckets
+                .iter()
+                .map(|(from, to, count)| {
+                    format!("{{\"from\":{from},\"to\":{to},\"count\":{count}}}")
+                })
+                .collect();
+            fields.push(format!("\"{name}_histogram\":[{}]", buckets.join(",")));
+        }
+        if let Some(sections) = &report.sections {
+            let sections: Vec<String> = sections
+                .iter()
+                .map(|section| {
+                    format!(
+                        "{{\"level\":{},\"heading\":{},\"words\":{}}}",
+                        section.level,
+                        json_string(&section.heading),
+                        section.words
+                    )
+                })
+                .collect();
+            fields.push(format!("\"sections\":[{}]", sections.join(",")));
+        }
+        if let Some(elements) = report.elements {
+            let elements: Vec<String> = elements
+                .iter()
+                .map(|(name, counts)| {
+                    format!(
+                        "{{\"element\":{},\"count\":{},\"words\":{}}}",
+                        json_string(name),
+                        counts.elements,
+                        counts.words
+                    )
+                })
+                .collect();
+            fields.push(format!("\"elements\":[{}]", elements.join(",")));
+        }
+        if let Some(cues) = &report.cues {
+            let cues: Vec<String> = cues
+                .iter()
+                .map(|(cue, fast)| {
+                    format!(
+                        "{{\"start\":{:.3},\"end\":{:.3},\"words\":{},\"wpm\":{},\"fast\":{fast}}}",
+                        cue.start.as_secs_f64(),
+                        cue.end.as_secs_f64(),
+                        cue.words,
+                        Value::decimal(cue.words_per_minute(), 1).to_json()
+                    )
+                })
+                .collect();
+            fields.push(format!("\"cues\":[{}]", cues.join(",")));
+        }
+        if let Some(records) = report.records {
+            let columns: Vec<String> = records
+                .columns()
+                .map(|(index, counts)| {
+                    format!(
+                        "{{\"column\":{index},\"name\":{},\"words\":{},\"chars\":{}}}",
+                        Value::Text(counts.name.clone()).to_json(),
+                        counts.words,
+                        counts.chars
+                    )
+                })
+                .collect();
+            fields.push(format!("\"columns\":[{}]", columns.join(",")));
+            let ragged: Vec<String> = records
+                .ragged()
+                .iter()
+                .map(|ragged| {
+                    format!(
+                        "{{\"record\":{},\"fields\":{}}}",
+                        ragged.record, ragged.fields
+                    )
+                })
+                .collect();
+            fields.push(format!("\"ragged_records\":[{}]", ragged.join(",")));
+        }
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Header of the CSV output: the file name and the names of the
+    /// columns, or the kind of entries that are listed instead
+    fn csv_header(&self, listed: Option<&str>) -> String {
+        if let Some(kind) = listed {
+            return format!("file,{kind},count");
+        }
+        std::iter::once("file")
+            .chain(
+                self.values(&Report::new("", Counts::default()))
+                    .iter()
+                    .map(|(name, _)| *name),
+            )
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    fn to_csv(&self, report: &Report) -> String {
+        std::iter::once(csv_field(report.name))
+            .chain(self.values(report).iter().map(|(_, value)| value.to_csv()))
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// How to print the counts
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// Aligned columns, followed by the file name
+    #[default]
+    Table,
+    /// A JSON object per line
+    Json,
+    /// Comma-separated values with a header.
+    /// Lists words or n-grams instead of the columns, if there are any.
+    /// Histograms, sections, elements, cues, and the columns and ragged
+    /// records of delimited data are left out.
+    Csv,
+}
+
+/// Which words or n-grams to list after the counts, most frequent first
+#[derive(Debug, Default, Clone, Copy)]
+struct Listing {
+    /// List at most this many, or all of them if `None`
+    top: Option<usize>,
+    /// List n-grams of this many words instead of single words
+    ngrams: Option<usize>,
+    /// List code points instead of words
+    code_points: bool,
+    /// Leave out words or n-grams that occur less often
+    min_count: usize,
+}
+
+impl Listing {
+    const fn enabled(&self) -> bool {
+        self.top.is_some() || self.ngrams.is_some() || self.code_points
+    }
+
+    const fn kind(&self) -> &'static str {
+        if self.ngrams.is_some() {
+            "ngram"
+        } else if self.code_points {
+            "code_point"
+        } else {
+            "word"
+        }
+    }
+
+    /// The words, n-grams, or code points to list with their counts, if any
+    fn entries<'a>(
+        &self,
+        frequencies: &'a Frequencies,
+        ngrams: Option<&'a NGrams>,
+        classes: Option<&CharClasses>,
+    ) -> Option<Vec<(Cow<'a, str>, usize)>> {
+        if !self.enabled() {
+            return None;
+        }
+        let mut entries: Vec<_> = match (ngrams, classes) {
+            (Some(ngrams), _) => ngrams
+                .sorted(self.min_count)
+                .into_iter()
+                .map(|(ngram, count)| (Cow::Borrowed(ngram), count))
+                .collect(),
+            (None, Some(classes)) if self.code_points => classes
+                .code_points()
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|&(_, count)| count >= self.min_count)
+                .map(|(c, count)| (Cow::Owned(code_point(c)), count))
+                .collect(),
+            _ => frequencies
+                .top(usize::MAX)
+                .into_iter()
+                .filter(|&(_, count)| count >= self.min_count)
+                .map(|(word, count)| (Cow::Borrowed(word), count))
+                .collect(),
+        };
+        if let Some(top) = self.top {
+            entries.truncate(top);
+        }
+        Some(entries)
+    }
+}
+
+/// A code point like `U+201C “`, leaving out the character itself if it
+/// would be invisible or garble the output
+fn code_point(c: char) -> String {
+    match CharClass::of(c) {
+        CharClass::Whitespace | CharClass::Control | CharClass::Other => {
+            format!("U+{:04X}", u32::from(c))
+        }
+        _ => format!("U+{:04X} {c}", u32::from(c)),
     }
+}
+
+/// Print the counts of a single input or the total.
+/// The listed words or n-grams follow with their counts,
+/// like `sort | uniq -c | sort -rn | head -n`, then the histograms, the
+/// words under each heading, the words and number of each element, the
+/// cues of subtitles, and the columns and ragged records of delimited
+/// data.
+fn print(columns: &Columns, format: Format, kind: &str, report: &Report) {
+    match (format, &report.entries) {
+        (Format::Table, entries) => {
+            println!("{}", columns.format(report));
+            for (entry, count) in entries.iter().flatten() {
+                println!("{count:>7} {entry}");
+            }
+            for (name, buckets) in report.histograms() {
+                for (from, to, count) in buckets {
+                    println!("{count:>7} {name} {from}-{to}");
+                }
+            }
+            for section in report.sections.iter().flatten() {
+                if section.level == 0 {
+                    println!("{:>7} (before the first heading)", section.words);
+                } else {
+                    let level = "#".repeat(section.level);
+                    println!("{:>7} {level} {}", section.words, section.heading);
+                }
+            }
+            for (name, counts) in report.elements.iter().flat_map(|elements| elements.iter()) {
+                println!("{:>7} {:>7} <{name}>", counts.words, counts.elements);
+            }
+            for (cue, fast) in report.cues.iter().flatten() {
+                println!(
+                    "{:>7} {:>7.3} {:>7} {} --> {}{}",
+                    cue.words,
+                    cue.duration().as_secs_f64(),
+                    Value::decimal(cue.words_per_minute(), 1).to_text(),
+                    clock(cue.start),
+                    clock(cue.end),
+                    if *fast { " (too fast)" } else { "" }
+                );
+            }
+            for (index, counts) in report.records.iter().flat_map(|records| records.columns()) {
+                let name = counts
+                    .name
+                    .as_ref()
+                    .map_or_else(String::new, |name| format!(" {name}"));
+                println!("{:>7} {:>7} #{index}{name}", counts.words, counts.chars);
+            }
+            for ragged in report.records.iter().flat_map(|records| records.ragged()) {
+                println!("{:>7} fields in record {}", ragged.fields, ragged.record);
+            }
+        }
+        (Format::Json, _) => println!("{}", columns.to_json(report, kind)),
+        (Format::Csv, Some(entries)) => {
+            for (entry, count) in entries {
+                println!("{},{},{count}", csv_field(report.name), csv_field(entry));
+            }
+        }
+        (Format::Csv, None) => println!("{}", columns.to_csv(report)),
+    }
+}
 
-    let mut total = Counts {
-        words: 0,
-        lines: 0,
-        characters: 0,
+/// A time like `01:02:03.004`, as in subtitles
+fn clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        time.subsec_millis()
+    )
+}
+
+/// Open a file, or standard input for `-`, with the files it includes
+/// in place if it's LaTeX source
+fn open(file: &str, latex: bool) -> Result<Box<dyn Read>> {
+    let reader: Box<dyn Read> = if file == STDIN {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(File::open(file)?)
     };
+    if !latex {
+        return Ok(reader);
+    }
+    // Included files are relative to the main document
+    let dir = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+    Ok(Box::new(TexInput::new(reader, dir)))
+}
 
-    for file in std::env::args().skip(1) {
-        let mut reader: Box<dyn Read> = if file == STDIN {
-            Box::new(std::io::stdin())
+/// Parse a positive number given with an option like `--top=N`
+fn parse_count(value: &str, what: &str) -> Result<usize> {
+    match value.parse() {
+        Ok(0) | Err(_) => Err(format!("Invalid {what}: {value}").into()),
+        Ok(n) => Ok(n),
+    }
+}
+
+/// Parse the delimiter of delimited data given with `--delimited`
+fn parse_delimiter(value: &str) -> Result<u8> {
+    match value.as_bytes() {
+        b"csv" => Ok(b','),
+        b"tsv" => Ok(b'\t'),
+        &[delimiter] if delimiter.is_ascii() => Ok(delimiter),
+        _ => Err(format!("Invalid delimiter: {value} (expected csv, tsv, or a character)").into()),
+    }
+}
+
+/// Command line arguments
+#[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+struct Args {
+    columns: Columns,
+    options: Options,
+    listing: Listing,
+    ignore_case: bool,
+    /// Count only the prose of Markdown text as words
+    markdown: bool,
+    /// Print the words under each Markdown heading
+    sections: bool,
+    /// Count the text of HTML or XML markup
+    markup: Option<Markup>,
+    /// Print the words and number of each element of the markup
+    elements: bool,
+    /// Count the words of LaTeX source, and follow the files it includes
+    latex: bool,
+    /// Count the text of SRT or `WebVTT` subtitles
+    subtitles: bool,
+    /// Print the words and reading speed of each cue of the subtitles
+    cues: bool,
+    /// Reading speed above which a cue is too fast, if not the default
+    max_wpm: Option<usize>,
+    /// Delimiter of the fields of delimited data like CSV, if it's counted
+    delimiter: Option<u8>,
+    /// Take the first record of delimited data as a header
+    header: bool,
+    /// Columns of delimited data to count, if not all
+    select: Option<Vec<Column>>,
+    /// Count the strings of JSON
+    json_strings: bool,
+    /// Path to the strings of JSON to count, if not all
+    json_path: Option<JsonPath>,
+    /// Lines that start the sections of each file, if they are counted
+    /// one by one
+    headings: Option<Headings>,
+    /// Width of the buckets of line histograms, if they are printed
+    histogram: Option<usize>,
+    format: Format,
+    files: Vec<String>,
+}
+
+impl Args {
+    fn max_wpm(&self) -> usize {
+        self.max_wpm.unwrap_or(DEFAULT_MAX_WPM)
+    }
+
+    /// Parse an option about the kind of the inputs, like `--markdown` or
+    /// `--delimited=csv`, and return whether it was one
+    fn parse_input_kind(&mut self, arg: &str) -> Result<bool> {
+        if arg == "--code" {
+            self.columns.code = true;
+        } else if arg == "--markdown" {
+            self.markdown = true;
+        } else if arg == "--code-blocks" {
+            self.markdown = true;
+            self.columns.code_blocks = true;
+        } else if arg == "--sections" {
+            self.markdown = true;
+            self.sections = true;
+        } else if arg == "--html" {
+            self.markup = Some(Markup::Html);
+        } else if arg == "--xml" {
+            self.markup = Some(Markup::Xml);
+        } else if arg == "--elements" {
+            self.elements = true;
+        } else if arg == "--latex" {
+            self.latex = true;
+            self.columns.latex = true;
+        } else if arg == "--subtitles" {
+            self.subtitles = true;
+        } else if arg == "--cues" {
+            self.subtitles = true;
+            self.cues = true;
+        } else if let Some(wpm) = arg.strip_prefix("--max-wpm=") {
+            self.subtitles = true;
+            self.max_wpm = Some(parse_count(wpm, "reading speed")?);
+        } else if let Some(delimiter) = arg.strip_prefix("--delimited=") {
+            self.delimiter = Some(parse_delimiter(delimiter)?);
+        } else if arg == "--header" {
+            self.header = true;
+        } else if let Some(columns) = arg.strip_prefix("--select=") {
+            self.select = Some(
+                columns
+                    .split(',')
+                    .map(str::parse)
+                    .collect::<std::result::Result<_, String>>()?,
+            );
+        } else if arg == "--json-strings" {
+            self.json_strings = true;
+        } else if let Some(path) = arg.strip_prefix("--json-path=") {
+            self.json_strings = true;
+            self.json_path = Some(path.parse()?);
+        } else if arg == "--by-heading" {
+            self.headings = Some(Headings::Markdown);
+        } else if let Some(pattern) = arg.strip_prefix("--by-heading=") {
+            self.headings = Some(
+                Headings::regex(pattern)
+                    .map_err(|e| format!("Invalid heading pattern: {pattern}\n{e}"))?,
+            );
         } else {
-            Box::new(File::open(&file)?)
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut parsed = Self {
+            listing: Listing {
+                min_count: 1,
+                ..Listing::default()
+            },
+            ..Self::default()
         };
+        for arg in args {
+            if parsed.parse_input_kind(&arg)? {
+                continue;
+            }
+            if let Some(n) = arg.strip_prefix("--top=") {
+                parsed.listing.top = Some(
+                    n.parse()
+                        .map_err(|_| format!("Invalid number of words: {n}"))?,
+                );
+            } else if let Some(n) = arg.strip_prefix("--ngrams=") {
+                parsed.listing.ngrams = Some(parse_count(n, "n-gram length")?);
+            } else if let Some(n) = arg.strip_prefix("--min-count=") {
+                parsed.listing.min_count = parse_count(n, "minimum count")?;
+            } else if arg == "--ignore-case" {
+                parsed.ignore_case = true;
+            } else if let Some(tab_width) = arg.strip_prefix("--tab-width=") {
+                parsed.options.tab_width = parse_count(tab_width, "tab width")?;
+            } else if let Some(word_boundary) = arg.strip_prefix("--words=") {
+                parsed.options.word_boundary = word_boundary.parse()?;
+            } else if let Some(encoding) = arg.strip_prefix("--encoding=") {
+                parsed.options.encoding = Some(encoding.parse()?);
+            } else if arg == "--line-stats" {
+                parsed.columns.line_stats = true;
+            } else if let Some(width) = arg.strip_prefix("--histogram=") {
+                parsed.histogram = Some(parse_count(width, "histogram bucket width")?);
+                parsed.columns.line_stats = true;
+            } else if arg == "--classes" {
+                parsed.columns.classes = true;
+            } else if arg == "--code-points" {
+                parsed.listing.code_points = true;
+            } else if arg == "--vocabulary" {
+                parsed.columns.vocabulary = true;
+            } else if arg == "--readability" {
+                parsed.columns.readability = true;
+            } else if arg == "--json" {
+                parsed.format = Format::Json;
+            } else if arg == "--csv" {
+                parsed.format = Format::Csv;
+            } else if arg == "--invalid" {
+                parsed.columns.invalid = true;
+            } else if arg == "--show-encoding" {
+                parsed.columns.encoding = true;
+            } else {
+                match arg.strip_prefix('-') {
+                    Some(flags) if !flags.is_empty() => parsed.columns.parse(flags)?,
+                    _ => parsed.files.push(arg),
+                }
+            }
+        }
+        if parsed.listing.code_points && parsed.listing.ngrams.is_some() {
+            return Err("Can't list n-grams and code points at the same time".into());
+        }
+        if parsed.elements && parsed.markup.is_none() {
+            parsed.markup = Some(Markup::Html);
+        }
+        let kinds = [
+            (parsed.subtitles, "subtitles"),
+            (parsed.delimiter.is_some(), "delimited data"),
+            (parsed.json_strings, "JSON"),
+        ];
+        let kinds: Vec<&str> = kinds
+            .into_iter()
+            .filter_map(|(given, kind)| given.then_some(kind))
+            .collect();
+        if kinds.len() > 1 {
+            return Err(format!("Can't count {} at the same time", kinds.join(" and ")).into());
+        }
+        if (parsed.header || parsed.select.is_some()) && parsed.delimiter.is_none() {
+            return Err("--header and --select need --delimited".into());
+        }
+        // Subtitles, delimited records, and strings of JSON are counted in
+        // addition to the default columns
+        parsed.columns = parsed.columns.or_default();
+        parsed.columns.subtitles = parsed.subtitles;
+        parsed.columns.records = parsed.delimiter.is_some();
+        parsed.columns.json_strings = parsed.json_strings;
+        parsed.options.decode = parsed.columns.needs_decoding();
+        // Readability scores are based on the number of sentences
+        parsed.options.prose =
+            parsed.columns.sentences || parsed.columns.paragraphs || parsed.columns.readability;
+        // Character classes and line lengths need characters, too
+        parsed.options.decode |=
+            parsed.columns.classes || parsed.listing.code_points || parsed.columns.line_stats;
+        Ok(parsed)
+    }
+}
 
-        let counts = counter(&mut reader)?;
+/// Metrics counted alongside the built-in counts of each input, if the
+/// arguments ask for them
+#[derive(Clone)]
+struct Metrics {
+    frequencies: Frequencies,
+    ngrams: Option<NGrams>,
+    readability: Readability,
+    classes: CharClasses,
+    line_stats: LineStats,
+    /// Language of the source code, if lines of code are counted
+    language: Option<Language>,
+    source_lines: Option<SourceLines>,
+    markdown: Markdown,
+    elements: Elements,
+    latex: Latex,
+    subtitles: Subtitles,
+    records: Records,
+    json_strings: JsonStrings,
+}
 
-        total += counts;
-        println!(
-            "{:>7} {:>7} {:>7} {}",
-            counts.lines, counts.words, counts.characters, file
+impl Metrics {
+    fn new(args: &Args, language: Option<Language>) -> Self {
+        let boundary = args.options.word_boundary;
+        let classes = CharClasses::default();
+        Self {
+            frequencies: Frequencies::new(boundary, args.ignore_case),
+            ngrams: args
+                .listing
+                .ngrams
+                .map(|n| NGrams::new(n, boundary, args.ignore_case)),
+            readability: Readability::default(),
+            classes: if args.listing.code_points {
+                classes.with_code_points()
+            } else {
+                classes
+            },
+            line_stats: LineStats::new(boundary),
+            language,
+            source_lines: None,
+            markdown: Markdown::new(boundary),
+            elements: Elements::default(),
+            latex: Latex::default(),
+            subtitles: Subtitles::new(args.max_wpm()),
+            records: Records::default(),
+            json_strings: JsonStrings::default(),
+        }
+    }
+
+    /// Count an input in a single pass, or the text of its markup,
+    /// subtitles, delimited data, or JSON
+    fn count(&mut self, input: &mut impl Read, args: &Args) -> Result<Counts> {
+        if args.subtitles {
+            let mut text = SubtitleText::new(input, args.options.word_boundary, args.max_wpm());
+            let counts = self.count_text(&mut text, args)?;
+            self.subtitles = text.into_subtitles();
+            return Ok(counts);
+        }
+        if let Some(delimiter) = args.delimiter {
+            let mut text = DelimitedText::new(input, delimiter, args.options.word_boundary);
+            if args.header {
+                text = text.with_header();
+            }
+            if let Some(columns) = &args.select {
+                text = text.with_columns(columns.clone());
+            }
+            let counts = self.count_text(&mut text, args)?;
+            self.records = text.into_records();
+            return Ok(counts);
+        }
+        if args.json_strings {
+            let mut text = JsonText::new(input, args.options.word_boundary);
+            if let Some(path) = &args.json_path {
+                text = text.with_path(path.clone());
+            }
+            let counts = self.count_text(&mut text, args)?;
+            self.json_strings = text.into_strings();
+            return Ok(counts);
+        }
+        let Some(markup) = args.markup else {
+            return self.count_text(input, args);
+        };
+        let mut text = MarkupText::new(input, markup);
+        let counts = self.count_text(&mut text, args)?;
+        self.elements += text.into_elements();
+        Ok(counts)
+    }
+
+    fn count_text(&mut self, input: &mut impl Read, args: &Args) -> Result<Counts> {
+        let (columns, listing) = (&args.columns, &args.listing);
+        let mut code = self.language.map(CodeLines::new);
+        let mut metrics: Vec<&mut dyn Metric> = Vec::new();
+        if let Some(ngrams) = self.ngrams.as_mut() {
+            metrics.push(ngrams);
+        }
+        if (listing.enabled() && listing.ngrams.is_none()) || columns.vocabulary {
+            metrics.push(&mut self.frequencies);
+        }
+        if columns.readability {
+            metrics.push(&mut self.readability);
+        }
+        if columns.classes || listing.code_points {
+            metrics.push(&mut self.classes);
+        }
+        if columns.line_stats {
+            metrics.push(&mut self.line_stats);
+        }
+        if let Some(code) = code.as_mut() {
+            metrics.push(code);
+        }
+        if args.markdown {
+            metrics.push(&mut self.markdown);
+        }
+        if args.latex {
+            metrics.push(&mut self.latex);
+        }
+        let mut counts = counter_with_metrics(input, &args.options, &mut metrics)?;
+        self.source_lines = code.as_ref().map(CodeLines::lines);
+        if args.markdown {
+            counts.words = self.markdown.words();
+        }
+        if args.latex {
+            counts.words = self.latex.counts().words();
+        }
+        Ok(counts)
+    }
+
+    fn report<'a>(&'a self, name: &'a str, counts: Counts, args: &Args) -> Report<'a> {
+        Report {
+            name,
+            counts,
+            scores: self.readability.scores(counts.sentences),
+            vocabulary: args
+                .columns
+                .vocabulary
+                .then(|| self.frequencies.vocabulary()),
+            classes: Some(&self.classes),
+            line_stats: Some(&self.line_stats),
+            language: self.language,
+            source_lines: self.source_lines,
+            code_blocks: Some(self.markdown.code_blocks()),
+            tex: Some(self.latex.counts()),
+            cue_totals: Some(self.subtitles.totals()),
+            cues: args.cues.then(|| {
+                self.subtitles
+                    .cues()
+                    .iter()
+                    .map(|cue| (*cue, self.subtitles.is_fast(cue)))
+                    .collect()
+            }),
+            records: args.delimiter.map(|_| &self.records),
+            json_strings: args.json_strings.then_some(&self.json_strings),
+            sections: args.sections.then(|| {
+                self.markdown
+                    .sections()
+                    .iter()
+                    .filter(|section| section.level > 0 || section.words > 0)
+                    .collect()
+            }),
+            elements: args.elements.then_some(&self.elements),
+            histogram: args.histogram,
+            entries: args.listing.entries(
+                &self.frequencies,
+                self.ngrams.as_ref(),
+                Some(&self.classes),
+            ),
+        }
+    }
+}
+
+/// Add up the metrics of separate inputs for the total.
+/// Vocabularies are merged, so words that occur in several inputs count
+/// as one distinct word.
+impl std::ops::AddAssign for Metrics {
+    fn add_assign(&mut self, other: Self) {
+        self.frequencies += other.frequencies;
+        if let (Some(ngrams), Some(other)) = (self.ngrams.as_mut(), other.ngrams) {
+            *ngrams += other;
+        }
+        self.readability += other.readability;
+        self.classes += other.classes;
+        self.line_stats += other.line_stats;
+        self.markdown += other.markdown;
+        self.elements += other.elements;
+        self.latex += other.latex;
+        self.subtitles += other.subtitles;
+        self.records += other.records;
+        self.json_strings += other.json_strings;
+        if self.language != other.language {
+            self.language = None;
+        }
+        self.source_lines = match (self.source_lines, other.source_lines) {
+            (Some(lines), Some(other)) => Some(lines + other),
+            (lines, other) => lines.or(other),
+        };
+    }
+}
+
+/// Count each section of a file and print it, and return the counts of
+/// the whole file
+fn count_sections(
+    reader: impl Read,
+    headings: &Headings,
+    file: &str,
+    language: Option<Language>,
+    args: &Args,
+) -> Result<(Counts, Metrics)> {
+    let mut splitter = SectionSplitter::new(BufReader::new(reader), headings.clone());
+    let mut counts = Counts::default();
+    let mut metrics = Metrics::new(args, language);
+    while let Some(mut section) = splitter.next_section()? {
+        let name = section.heading().map_or_else(
+            || format!("{file}: (before the first heading)"),
+            |heading| format!("{file}: {heading}"),
         );
+        let mut section_metrics = Metrics::new(args, language);
+        let mut section_counts = section_metrics.count(&mut section, args)?;
+        // Number lines and offsets within the whole file
+        if section_counts.longest_line > 0 {
+            section_counts.longest_line += counts.lines;
+        }
+        section_counts.first_invalid = section_counts
+            .first_invalid
+            .map(|offset| offset + counts.bytes);
+        let (columns, format, kind) = (&args.columns, args.format, args.listing.kind());
+        print(
+            columns,
+            format,
+            kind,
+            &section_metrics.report(&name, section_counts, args),
+        );
+        counts += section_counts;
+        metrics += section_metrics;
+    }
+    Ok((counts, metrics))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse(std::env::args().skip(1))?;
+    if args.files.is_empty() {
+        eprintln!("{USAGE}");
+        return Err("Could not read file".into());
     }
 
-    println!(
-        "{:>7} {:>7} {:>7} total",
-        total.lines, total.words, total.characters
+    let (columns, format, kind) = (&args.columns, args.format, args.listing.kind());
+    if format == Format::Csv {
+        let listed = args.listing.enabled().then_some(kind);
+        println!("{}", columns.csv_header(listed));
+    }
+
+    let mut total = Counts::default();
+    let mut total_metrics = Metrics::new(&args, None);
+    // Totals per language of the source code, if lines of code are counted
+    let mut languages = BTreeMap::new();
+    for file in &args.files {
+        let mut reader = open(file, args.latex)?;
+        let language = Language::from_path(file).filter(|_| columns.code);
+        let (counts, metrics) = if let Some(headings) = &args.headings {
+            count_sections(reader, headings, file, language, &args)?
+        } else {
+            let mut metrics = Metrics::new(&args, language);
+            (metrics.count(&mut reader, &args)?, metrics)
+        };
+        print(columns, format, kind, &metrics.report(file, counts, &args));
+        if let Some(language) = language {
+            let (language_total, language_metrics) = languages
+                .entry(language)
+                .or_insert_with(|| (Counts::default(), Metrics::new(&args, Some(language))));
+            *language_total += counts;
+            *language_metrics += metrics.clone();
+        }
+        total += counts;
+        total_metrics += metrics;
+    }
+    for (language, (counts, metrics)) in &languages {
+        print(
+            columns,
+            format,
+            kind,
+            &metrics.report(language.name(), *counts, &args),
+        );
+    }
+    print(
+        columns,
+        format,
+        kind,
+        &total_metrics.report("total", total, &args),
     );
 
     Ok(())
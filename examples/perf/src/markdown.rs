@@ -0,0 +1,786 @@
+use crate::metric::{Chunk, Metric};
+use crate::resume::{Resumable, Resume};
+use crate::{WordBoundary, Words};
+
+/// Fenced and indented code blocks, which aren't counted as prose
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CodeBlocks {
+    pub blocks: usize,
+    /// Lines inside the blocks, without the fences
+    pub lines: usize,
+    pub words: usize,
+}
+
+impl CodeBlocks {
+    /// Counted since `earlier`, a count of the same text so far
+    const fn since(self, earlier: Self) -> Self {
+        Self {
+            blocks: self.blocks - earlier.blocks,
+            lines: self.lines - earlier.lines,
+            words: self.words - earlier.words,
+        }
+    }
+}
+
+/// Add up the code blocks of separate inputs, e.g. for a total over
+/// several files
+impl std::ops::AddAssign for CodeBlocks {
+    fn add_assign(&mut self, other: Self) {
+        self.blocks += other.blocks;
+        self.lines += other.lines;
+        self.words += other.words;
+    }
+}
+
+/// The text under a heading, up to the next one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    /// From 1 for `#` or a `===` underline to 6 for `######`, or 0 for the
+    /// text before the first heading
+    pub level: usize,
+    /// Text of the heading, as a reader sees it
+    pub heading: String,
+    /// Words of the prose, including the heading's
+    pub words: usize,
+}
+
+/// Where the parser is at the start of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+    /// The first line, which might start YAML front matter
+    Start,
+    Text,
+    FrontMatter,
+    /// Inside a code block fenced by at least `len` backticks or tildes
+    Fence {
+        marker: u8,
+        len: usize,
+    },
+    /// Inside a code block indented by four spaces, after blank lines that
+    /// are only part of it if more code follows
+    Indented {
+        blank_lines: usize,
+    },
+}
+
+/// What a line of Markdown is, as far as blocks go
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Line<'a> {
+    /// Front matter, blank lines, closing fences, thematic breaks, and link
+    /// reference definitions, which have no words
+    Markup,
+    /// Opens a code block fenced with backticks or tildes
+    Fence,
+    /// A line of code, after `blank_lines` blank lines of the same block.
+    /// It `opens` a code block indented by four spaces.
+    Code { opens: bool, blank_lines: usize },
+    /// An ATX heading like `## Usage`, with its level and text
+    Heading { level: usize, text: &'a str },
+    /// Underlines the plain text right before, which makes it a setext
+    /// heading of the given level
+    Underline { level: usize },
+    /// Text without markers like those of lists and block quotes. `plain`
+    /// text outside of lists can be underlined to make it a heading.
+    Text { text: &'a str, plain: bool },
+}
+
+/// Tells the blocks of Markdown apart line by line
+#[derive(Debug, Clone)]
+pub(crate) struct Blocks {
+    block: Block,
+    /// No paragraph goes on at the current line, so an indented line
+    /// starts a code block
+    after_block: bool,
+    /// Inside a list, where indented lines go on with an item instead
+    in_list: bool,
+    /// The line before was plain text that an underline makes a heading
+    in_paragraph: bool,
+}
+
+impl Default for Blocks {
+    fn default() -> Self {
+        Self {
+            block: Block::Start,
+            after_block: true,
+            in_list: false,
+            in_paragraph: false,
+        }
+    }
+}
+
+impl Blocks {
+    /// What a line is, without its line break. Lines inside an HTML
+    /// comment are text as they are, unless they're part of code.
+    pub(crate) fn line<'a>(&mut self, line: &'a str, in_comment: bool) -> Line<'a> {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match self.block {
+            Block::Start if line.trim_end() == "---" => {
+                self.block = Block::FrontMatter;
+                return Line::Markup;
+            }
+            Block::FrontMatter => {
+                if matches!(line.trim_end(), "---" | "...") {
+                    self.block = Block::Text;
+                }
+                return Line::Markup;
+            }
+            Block::Fence { marker, len } => {
+                if is_closing_fence(line.trim_start(), marker, len) {
+                    self.block = Block::Text;
+                    return Line::Markup;
+                }
+                return Line::Code {
+                    opens: false,
+                    blank_lines: 0,
+                };
+            }
+            Block::Indented { blank_lines } => {
+                if line.trim().is_empty() {
+                    self.block = Block::Indented {
+                        blank_lines: blank_lines + 1,
+                    };
+                    return Line::Markup;
+                }
+                if indentation(line) >= 4 {
+                    self.block = Block::Indented { blank_lines: 0 };
+                    return Line::Code {
+                        opens: false,
+                        blank_lines,
+                    };
+                }
+                self.block = Block::Text;
+            }
+            Block::Start | Block::Text => self.block = Block::Text,
+        }
+
+        let text = line.trim_start();
+        if text.is_empty() {
+            self.after_block = true;
+            self.in_paragraph = false;
+            return Line::Markup;
+        }
+        if in_comment {
+            self.in_paragraph = false;
+            return Line::Text { text, plain: false };
+        }
+
+        let indent = indentation(line);
+        if indent >= 4 && self.after_block && !self.in_list {
+            self.block = Block::Indented { blank_lines: 0 };
+            return Line::Code {
+                opens: true,
+                blank_lines: 0,
+            };
+        }
+        let stripped = strip_markers(text);
+        let is_list_item = stripped.len() < text.len() && !text.starts_with('>');
+        if is_list_item {
+            self.in_list = true;
+        } else if indent == 0 && self.after_block {
+            self.in_list = false;
+        }
+        let in_paragraph = std::mem::take(&mut self.in_paragraph);
+        self.after_block = true;
+
+        // Fences are allowed at any indentation, e.g. in list items
+        if let Some((marker, len)) = opening_fence(text) {
+            self.block = Block::Fence { marker, len };
+            return Line::Fence;
+        }
+        if let Some(level) = underline(text).filter(|_| in_paragraph && indent < 4) {
+            return Line::Underline { level };
+        }
+        if is_rule(text) || is_reference(text) {
+            return Line::Markup;
+        }
+        if let Some((level, text)) = heading(text).filter(|_| indent < 4) {
+            return Line::Heading { level, text };
+        }
+        self.after_block = false;
+        // Only plain text outside of lists can be a setext heading
+        self.in_paragraph = !self.in_list && stripped.len() == text.len();
+        Line::Text {
+            text: stripped,
+            plain: self.in_paragraph,
+        }
+    }
+}
+
+/// Lines of plain text so far, which a setext heading underline turns into
+/// a heading
+#[derive(Debug, Default, Clone)]
+struct Paragraph {
+    text: String,
+    words: usize,
+}
+
+/// Tells prose from markup line by line
+#[derive(Debug, Clone)]
+struct Parser {
+    /// Counts the words of a line when cloned, as it's cheaper than
+    /// creating a new one each time
+    line_words: Words,
+    blocks: Blocks,
+    /// Inside an HTML comment, which can span lines
+    in_comment: bool,
+    paragraph: Option<Paragraph>,
+    /// The current line so far
+    line: String,
+    words: usize,
+    code: CodeBlocks,
+    /// Never empty, as the text before the first heading has a section,
+    /// too
+    sections: Vec<Section>,
+}
+
+impl Parser {
+    fn new(boundary: WordBoundary) -> Self {
+        Self {
+            line_words: Words::new(boundary),
+            blocks: Blocks::default(),
+            in_comment: false,
+            paragraph: None,
+            line: String::new(),
+            words: 0,
+            code: CodeBlocks::default(),
+            sections: vec![Section {
+                level: 0,
+                heading: String::new(),
+                words: 0,
+            }],
+        }
+    }
+}
+
+impl Resume for Parser {
+    fn update(&mut self, text: &str) {
+        let mut rest = text;
+        while let Some(end) = rest.find('\n') {
+            let mut line = std::mem::take(&mut self.line);
+            line.push_str(&rest[..end]);
+            self.add(&line);
+            line.clear();
+            self.line = line;
+            rest = &rest[end + 1..];
+        }
+        self.line.push_str(rest);
+    }
+
+    fn finish(&mut self) {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.add(&line);
+        }
+    }
+
+    /// What was counted after the head continues the last section counted
+    /// so far
+    fn resume(&mut self, next: Self, end: &Self) {
+        self.words += next.words - end.words;
+        self.code += next.code.since(end.code);
+        let mut sections = next.sections.into_iter().skip(end.sections.len() - 1);
+        if let (Some(first), Some(last)) = (sections.next(), self.sections.last_mut()) {
+            // A setext heading can take words back out of the section
+            last.words = last.words + first.words - end.sections[end.sections.len() - 1].words;
+        }
+        self.sections.extend(sections);
+        self.blocks = next.blocks;
+        self.in_comment = next.in_comment;
+        self.paragraph = next.paragraph;
+        self.line = next.line;
+    }
+}
+
+impl Parser {
+    fn count_words(&self, text: &str) -> usize {
+        let mut words = self.line_words.clone();
+        words.update(&Chunk::from_text(text));
+        words.finish();
+        words.count()
+    }
+
+    fn add_words(&mut self, words: usize) {
+        self.words += words;
+        if let Some(section) = self.sections.last_mut() {
+            section.words += words;
+        }
+    }
+
+    /// Count a line, without its line break
+    fn add(&mut self, line: &str) {
+        let paragraph = self.paragraph.take();
+        match self.blocks.line(line, self.in_comment) {
+            Line::Markup => {}
+            Line::Fence => self.code.blocks += 1,
+            Line::Code { opens, blank_lines } => {
+                self.code.blocks += usize::from(opens);
+                self.code.lines += blank_lines + 1;
+                self.code.words += self.count_words(line);
+            }
+            Line::Underline { level } => {
+                let paragraph = paragraph.unwrap_or_default();
+                // The paragraph was counted as part of the section before
+                if let Some(section) = self.sections.last_mut() {
+                    section.words -= paragraph.words;
+                }
+                self.sections.push(Section {
+                    level,
+                    heading: paragraph
+                        .text
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    words: paragraph.words,
+                });
+            }
+            Line::Heading { level, text } => {
+                let heading = self.inline(text);
+                let words = self.count_words(&heading);
+                self.sections.push(Section {
+                    level,
+                    heading: heading.split_whitespace().collect::<Vec<_>>().join(" "),
+                    words: 0,
+                });
+                self.add_words(words);
+            }
+            Line::Text { text, plain } => {
+                let prose = self.inline(text);
+                let words = self.count_words(&prose);
+                self.add_words(words);
+                if plain {
+                    let mut paragraph = paragraph.unwrap_or_default();
+                    paragraph.text.push(' ');
+                    paragraph.text.push_str(&prose);
+                    paragraph.words += words;
+                    self.paragraph = Some(paragraph);
+                }
+            }
+        }
+    }
+
+    /// The text a reader sees in `text`, without links, images, and HTML
+    fn inline(&mut self, text: &str) -> String {
+        let mut prose = String::with_capacity(text.len());
+        let mut rest = text;
+        while !rest.is_empty() {
+            if self.in_comment {
+                let Some(end) = rest.find("-->") else {
+                    break;
+                };
+                self.in_comment = false;
+                rest = &rest[end + 3..];
+                continue;
+            }
+            let Some(i) = rest.find(['\\', '`', '!', '[', ']', '<', '|']) else {
+                prose.push_str(rest);
+                break;
+            };
+            prose.push_str(&rest[..i]);
+            let special = &rest[i..];
+            let after = &special[1..];
+            rest = match special.as_bytes()[0] {
+                b'\\' => match after.chars().next() {
+                    Some(c) if c.is_ascii_punctuation() => {
+                        prose.push(c);
+                        &after[1..]
+                    }
+                    _ => {
+                        prose.push('\\');
+                        after
+                    }
+                },
+                // Code spans are seen as they are
+                b'`' => {
+                    let len = special.bytes().take_while(|&b| b == b'`').count();
+                    let (fence, code) = special.split_at(len);
+                    if let Some(end) = code.find(fence) {
+                        prose.push_str(&code[..end]);
+                        &code[end + len..]
+                    } else {
+                        prose.push_str(fence);
+                        code
+                    }
+                }
+                // Images are left out, even their alternative text
+                b'!' if after.starts_with('[') => match after.find(']') {
+                    Some(end) => skip_destination(&after[end + 1..]),
+                    None => {
+                        prose.push('!');
+                        after
+                    }
+                },
+                // Links leave their text
+                b'[' => after,
+                b']' => skip_destination(after),
+                b'<' if after.starts_with("!--") => {
+                    self.in_comment = true;
+                    &after[3..]
+                }
+                // Tags and autolinks
+                b'<' if after.starts_with(|c: char| c.is_ascii_alphabetic() || c == '/') => {
+                    match after.find('>') {
+                        Some(end) => {
+                            prose.push(' ');
+                            &after[end + 1..]
+                        }
+                        None => {
+                            prose.push('<');
+                            after
+                        }
+                    }
+                }
+                // Separates table cells
+                b'|' => {
+                    prose.push(' ');
+                    after
+                }
+                other => {
+                    prose.push(char::from(other));
+                    after
+                }
+            };
+        }
+        prose
+    }
+}
+
+/// The marker, length, and info string of a code fence like "```rust"
+fn fence(text: &str) -> Option<(u8, usize, &str)> {
+    let marker = *text.as_bytes().first()?;
+    if marker != b'`' && marker != b'~' {
+        return None;
+    }
+    let len = text.bytes().take_while(|&b| b == marker).count();
+    (len >= 3).then(|| (marker, len, text[len..].trim()))
+}
+
+/// The marker and length of a fence that opens a code block, at the start
+/// of `text`
+fn opening_fence(text: &str) -> Option<(u8, usize)> {
+    fence(text)
+        .filter(|&(marker, _, info)| marker == b'~' || !info.contains('`'))
+        .map(|(marker, len, _)| (marker, len))
+}
+
+/// Whether `text` closes a code block opened by a fence of `len` times
+/// `marker`
+fn is_closing_fence(text: &str, marker: u8, len: usize) -> bool {
+    fence(text).is_some_and(|(m, l, info)| m == marker && l >= len && info.is_empty())
+}
+
+/// Columns a line is indented by, with tabs up to the next multiple of 4
+fn indentation(line: &str) -> usize {
+    line.chars()
+        .take_while(|&c| c == ' ' || c == '\t')
+        .fold(0, |column, c| match c {
+            '\t' => column + 4 - column % 4,
+            _ => column + 1,
+        })
+}
+
+/// Level of a setext heading underline, 1 for `===` and 2 for `---`
+fn underline(text: &str) -> Option<usize> {
+    let text = text.trim_end();
+    let level = match text.as_bytes().first()? {
+        b'=' => 1,
+        b'-' => 2,
+        _ => return None,
+    };
+    text.bytes()
+        .all(|b| b == text.as_bytes()[0])
+        .then_some(level)
+}
+
+/// Thematic breaks, setext heading underlines without a paragraph, and
+/// table delimiter rows like `|---|:--:|`, which have no words
+fn is_rule(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| "-=*_|: \t".contains(c))
+}
+
+/// Link reference definitions like `[label]: https://...`
+fn is_reference(text: &str) -> bool {
+    text.starts_with('[')
+        && text
+            .find("]:")
+            .is_some_and(|end| !text[1..end].contains(']'))
+}
+
+/// Level and text of an ATX heading like `## Usage`, without the
+/// indentation of its line
+fn heading(text: &str) -> Option<(usize, &str)> {
+    let level = text.bytes().take_while(|&b| b == b'#').count();
+    let rest = &text[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+        return None;
+    }
+    // Leave out a closing sequence like in `## Usage ##`, but not the "#"
+    // of "C#"
+    let rest = rest.trim();
+    let without = rest.trim_end_matches('#');
+    Some((
+        level,
+        if without.is_empty() || without.ends_with([' ', '\t']) {
+            without.trim_end()
+        } else {
+            rest
+        },
+    ))
+}
+
+/// Strip block quote markers, list markers, and task list checkboxes from
+/// the start of a line
+fn strip_markers(mut text: &str) -> &str {
+    loop {
+        let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+        let marker = if text.starts_with(['>', '-', '*', '+']) {
+            1
+        } else if (1..=9).contains(&digits) && text[digits..].starts_with(['.', ')']) {
+            digits + 1
+        } else if ["[ ]", "[x]", "[X]"]
+            .iter()
+            .any(|box_| text.starts_with(box_))
+        {
+            3
+        } else {
+            return text;
+        };
+        let rest = &text[marker..];
+        // List markers are followed by whitespace, unlike emphasis
+        let ends = rest.is_empty() || rest.starts_with([' ', '\t']);
+        if !(ends || text.starts_with('>')) {
+            return text;
+        }
+        text = rest.trim_start();
+    }
+}
+
+/// Skip the destination right after the text of a link, like `(url)` or
+/// `[label]`
+fn skip_destination(text: &str) -> &str {
+    match text.as_bytes().first() {
+        Some(b'(') => {
+            // URLs can have balanced parentheses in them
+            let mut depth = 0;
+            for (i, b) in text.bytes().enumerate() {
+                match b {
+                    b'(' => depth += 1,
+                    b')' if depth == 1 => return &text[i + 1..],
+                    b')' => depth -= 1,
+                    _ => {}
+                }
+            }
+            text
+        }
+        Some(b'[') => text.find(']').map_or(text, |end| &text[end + 1..]),
+        _ => text,
+    }
+}
+
+/// Words of the prose of Markdown text, as a reader of the rendered text
+/// sees it
+///
+/// Leaves out YAML front matter, code blocks, HTML tags and
+/// comments, images, the destinations of links, link reference
+/// definitions, and markers like those of lists, block quotes, headings,
+/// and tables. The text of links and inline code counts.
+/// Code blocks are counted separately, and words are broken down by the
+/// heading they're under.
+///
+/// Both ATX headings like `## Usage` and setext headings underlined with
+/// `===` or `---` start sections. Like for
+/// [`CodeLines`](crate::CodeLines), pieces of the input counted on their
+/// own assume they start a document and keep their start to count again
+/// once the text before is known.
+#[derive(Debug, Clone)]
+pub struct Markdown {
+    parser: Resumable<Parser>,
+}
+
+impl Markdown {
+    pub fn new(boundary: WordBoundary) -> Self {
+        Self {
+            parser: Resumable::new(Parser::new(boundary)),
+        }
+    }
+
+    /// Words of the prose, including headings
+    pub const fn words(&self) -> usize {
+        self.parser.get().words
+    }
+
+    pub const fn code_blocks(&self) -> CodeBlocks {
+        self.parser.get().code
+    }
+
+    /// Sections in the order they appear, starting with the text before
+    /// the first heading
+    pub fn sections(&self) -> &[Section] {
+        &self.parser.get().sections
+    }
+}
+
+impl Metric for Markdown {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        self.parser.update(text);
+    }
+
+    fn finish(&mut self) {
+        self.parser.finish();
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.parser.merge(next.parser);
+    }
+}
+
+/// Add up the words and code blocks of separate inputs, e.g. for a total
+/// over several files.
+/// Sections belong to a single input, so they're left out.
+impl std::ops::AddAssign for Markdown {
+    fn add_assign(&mut self, other: Self) {
+        let (parser, other) = (self.parser.get_mut(), other.parser.get());
+        parser.words += other.words;
+        parser.code += other.code;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resume::MAX_HEAD_LEN;
+
+    fn count(pieces: &[&str]) -> Markdown {
+        let mut merged = Markdown::new(WordBoundary::Ascii);
+        for piece in pieces {
+            let mut markdown = Markdown::new(WordBoundary::Ascii);
+            markdown.update(&Chunk::from_text(piece));
+            merged.merge(markdown);
+        }
+        merged.finish();
+        merged
+    }
+
+    #[test]
+    fn test_inline() {
+        for (text, words) in [
+            ("See [the docs](https://example.com/a_(b)) now", 4),
+            ("![Banner](banner.png) Hello", 1),
+            ("[reference][1] and <https://example.com>", 2),
+            ("<div class=\"x\">Some <b>bold</b> text</div>", 3),
+            ("Run `cargo test --release` once", 5),
+            ("| a | b |\n|---|:-:|\n| c | d |", 4),
+            ("- [x] done\n1. first\n> quoted *emphasis*", 4),
+            ("a <!-- hidden\nstill hidden --> b", 2),
+            ("\\[not a link\\]", 3),
+            ("[1]: https://example.com", 0),
+        ] {
+            assert_eq!(count(&[text]).words(), words, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        let text = "---\ntitle: Front matter\n---\n# Title #\n\nIntro text.\n\
+                    \n```rust\nfn main() {}\n```\n\n## C# and more\n\n~~~\n```\nnot closed\n~~~\n***\nEnd";
+        let markdown = count(&[text]);
+        assert_eq!(markdown.words(), 7);
+        assert_eq!(
+            markdown.code_blocks(),
+            CodeBlocks {
+                blocks: 2,
+                lines: 3,
+                words: 6
+            }
+        );
+        let sections: Vec<_> = markdown
+            .sections()
+            .iter()
+            .map(|section| (section.level, section.heading.as_str(), section.words))
+            .collect();
+        assert_eq!(
+            sections,
+            [(0, "", 0), (1, "Title", 3), (2, "C# and more", 4)]
+        );
+    }
+
+    #[test]
+    fn test_indented_code() {
+        let text = "Intro\n    not code\n\n    let a = 1;\n\n\tlet b = 2;\n\n\nText\n\
+                    - item\n\n    more of the item\n";
+        let markdown = count(&[text]);
+        assert_eq!(markdown.words(), 9);
+        assert_eq!(
+            markdown.code_blocks(),
+            CodeBlocks {
+                blocks: 1,
+                lines: 3,
+                words: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_setext_headings() {
+        let text = "Intro\n\nThe *Title*\n===\n\nText\nmore text\n---\n\n---\n\
+                    - item\n---\nEnd";
+        let markdown = count(&[text]);
+        assert_eq!(markdown.words(), 8);
+        let sections: Vec<_> = markdown
+            .sections()
+            .iter()
+            .map(|section| (section.level, section.heading.as_str(), section.words))
+            .collect();
+        assert_eq!(
+            sections,
+            [(0, "", 1), (1, "The *Title*", 2), (2, "Text more text", 5)]
+        );
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text =
+            "---\na: b\n---\nText [x](y)\n# A\n```\nc d\n```\n<!--\ne\n-->\n## B <i>f</i>\ng\n\
+                    \n    h i\n\nC\n=\nj";
+        let expected = count(&[text]);
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first) {
+                let markdown = count(&[&text[..first], &text[first..second], &text[second..]]);
+                assert_eq!(
+                    (
+                        markdown.words(),
+                        markdown.code_blocks(),
+                        markdown.sections()
+                    ),
+                    (
+                        expected.words(),
+                        expected.code_blocks(),
+                        expected.sections()
+                    ),
+                    "split at {first} and {second}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_long_head() {
+        // Counted in two chunks, so only the first is counted again
+        let long = "x\n".repeat(MAX_HEAD_LEN);
+        let text = format!("# A\n{long}## B\n{long}");
+        let expected = count(&[&text]);
+        let (first, second) = text.split_at(MAX_HEAD_LEN);
+        let mut merged = Markdown::new(WordBoundary::Ascii);
+        merged.update(&Chunk::from_text(first));
+        let mut next = Markdown::new(WordBoundary::Ascii);
+        next.update(&Chunk::from_text(&second[..MAX_HEAD_LEN + 1000]));
+        next.update(&Chunk::from_text(&second[MAX_HEAD_LEN + 1000..]));
+        merged.merge(next);
+        merged.finish();
+        assert_eq!(merged.words(), expected.words());
+        assert_eq!(merged.sections(), expected.sections());
+    }
+}
@@ -0,0 +1,734 @@
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind, Read};
+
+/// Size of the buffer markup is read into
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Longest entity name, like `&thetasym;`, or number, like `&#x10FFFF;`
+const MAX_ENTITY_LEN: usize = 32;
+
+/// Named entities that are decoded, the most common ones of HTML and
+/// the five of XML
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("shy", '\u{AD}'),
+    ("ensp", '\u{2002}'),
+    ("emsp", '\u{2003}'),
+    ("thinsp", '\u{2009}'),
+    ("zwnj", '\u{200C}'),
+    ("zwj", '\u{200D}'),
+    ("ndash", '–'),
+    ("mdash", '—'),
+    ("lsquo", '‘'),
+    ("rsquo", '’'),
+    ("sbquo", '‚'),
+    ("ldquo", '“'),
+    ("rdquo", '”'),
+    ("bdquo", '„'),
+    ("laquo", '«'),
+    ("raquo", '»'),
+    ("hellip", '…'),
+    ("bull", '•'),
+    ("middot", '·'),
+    ("copy", '©'),
+    ("reg", '®'),
+    ("trade", '™'),
+    ("deg", '°'),
+    ("plusmn", '±'),
+    ("times", '×'),
+    ("divide", '÷'),
+    ("sect", '§'),
+    ("para", '¶'),
+    ("cent", '¢'),
+    ("pound", '£'),
+    ("euro", '€'),
+    ("yen", '¥'),
+    ("auml", 'ä'),
+    ("ouml", 'ö'),
+    ("uuml", 'ü'),
+    ("Auml", 'Ä'),
+    ("Ouml", 'Ö'),
+    ("Uuml", 'Ü'),
+    ("szlig", 'ß'),
+    ("eacute", 'é'),
+    ("egrave", 'è'),
+    ("aacute", 'á'),
+    ("agrave", 'à'),
+    ("ccedil", 'ç'),
+    ("ntilde", 'ñ'),
+];
+
+/// HTML elements that never have content or an end tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// HTML elements that start on a line of their own
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "br",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "title",
+    "tr",
+    "ul",
+];
+
+/// HTML elements whose end tag can be left out when the next one starts
+const OPTIONAL_END: &[&str] = &["dd", "dt", "li", "option", "p", "td", "th", "tr"];
+
+/// HTML elements whose content isn't text a reader sees
+const RAW_TEXT_ELEMENTS: &[&str] = &["script", "style"];
+
+/// A markup language to extract text from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Markup {
+    /// Case-insensitive tags, void elements like `<br>`, and `<script>`
+    /// and `<style>`, whose content is skipped
+    Html,
+    /// Every tag separates words, and only `/>` closes an element
+    /// without an end tag
+    Xml,
+}
+
+/// Number of elements with the same name and the words in them
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ElementCounts {
+    pub elements: usize,
+    /// Words anywhere inside the elements, including nested ones.
+    /// Words are separated by ASCII whitespace.
+    pub words: usize,
+}
+
+/// [`ElementCounts`] for each element name
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Elements {
+    counts: BTreeMap<String, ElementCounts>,
+}
+
+impl Elements {
+    pub fn get(&self, name: &str) -> ElementCounts {
+        self.counts.get(name).copied().unwrap_or_default()
+    }
+
+    /// All element names with their counts, sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = (&str, ElementCounts)> {
+        self.counts
+            .iter()
+            .map(|(name, &counts)| (name.as_str(), counts))
+    }
+
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+}
+
+/// Add up the elements of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for Elements {
+    fn add_assign(&mut self, other: Self) {
+        for (name, counts) in other.counts {
+            let total = self.counts.entry(name).or_default();
+            total.elements += counts.elements;
+            total.words += counts.words;
+        }
+    }
+}
+
+/// Where the tokenizer is in the markup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Text,
+    /// After `<`
+    TagOpen,
+    /// In the name of a start or end tag
+    TagName {
+        end: bool,
+    },
+    /// After the name of a tag, up to `>`. `slash` is whether the last
+    /// byte that isn't whitespace is a `/`.
+    Attributes {
+        end: bool,
+        quote: Option<u8>,
+        slash: bool,
+    },
+    /// After `<!`, until it's clear whether a comment, a CDATA section, or
+    /// a declaration like `<!DOCTYPE html>` follows
+    Declaration,
+    /// Inside a comment, after this many dashes in a row
+    Comment {
+        dashes: usize,
+    },
+    /// Inside a CDATA section, after this many `]` in a row
+    CData {
+        brackets: usize,
+    },
+    /// Skipped up to the next `>`, e.g. a processing instruction
+    Skip,
+    /// After `&`
+    Entity,
+    /// Inside a `<script>` or `<style>` element, after this many bytes of
+    /// its end tag
+    RawText {
+        matched: usize,
+    },
+}
+
+/// A tolerant tokenizer that turns markup into its text, byte by byte
+///
+/// Markup that isn't well-formed, like a `<` on its own or an unknown
+/// entity, is passed on as text rather than rejected.
+#[derive(Debug, Clone)]
+struct Tokenizer {
+    markup: Markup,
+    state: State,
+    /// Name of the current tag, or the current entity or declaration so
+    /// far
+    buffer: Vec<u8>,
+    /// Names of the open elements, innermost last
+    stack: Vec<String>,
+    /// Separator to emit before the next text, if it doesn't start with
+    /// one itself
+    pending: Option<u8>,
+    /// Last byte of text emitted so far
+    last: Option<u8>,
+    elements: Elements,
+}
+
+impl Tokenizer {
+    fn new(markup: Markup) -> Self {
+        Self {
+            markup,
+            state: State::Text,
+            buffer: Vec::new(),
+            stack: Vec::new(),
+            pending: None,
+            last: None,
+            elements: Elements::default(),
+        }
+    }
+
+    /// Emit a byte of text
+    fn emit(&mut self, byte: u8, text: &mut Vec<u8>) {
+        let space = byte.is_ascii_whitespace();
+        if let Some(separator) = self.pending {
+            if !space {
+                text.push(separator);
+                self.last = Some(separator);
+                self.pending = None;
+            } else if byte == b'\n' || separator == b' ' {
+                self.pending = None;
+            }
+        }
+        if !space && self.last.is_none_or(|last| last.is_ascii_whitespace()) {
+            self.start_word();
+        }
+        text.push(byte);
+        self.last = Some(byte);
+    }
+
+    fn emit_all(&mut self, bytes: &[u8], text: &mut Vec<u8>) {
+        for &byte in bytes {
+            self.emit(byte, text);
+        }
+    }
+
+    /// A word starts, so it counts for every element it's in
+    fn start_word(&mut self) {
+        for (i, name) in self.stack.iter().enumerate() {
+            // Elements on the stack have been counted when they started
+            if let (false, Some(counts)) = (
+                self.stack[..i].contains(name),
+                self.elements.counts.get_mut(name),
+            ) {
+                counts.words += 1;
+            }
+        }
+    }
+
+    /// Separate the text before from the text after, with a line break
+    /// or a space
+    fn separate(&mut self, separator: u8) {
+        let Some(last) = self.last else {
+            return;
+        };
+        if last == b'\n' || (separator == b' ' && last.is_ascii_whitespace()) {
+            return;
+        }
+        if self.pending != Some(b'\n') {
+            self.pending = Some(separator);
+        }
+    }
+
+    fn step(&mut self, byte: u8, text: &mut Vec<u8>) {
+        match self.state {
+            State::Text => match byte {
+                b'<' => self.state = State::TagOpen,
+                b'&' => {
+                    self.buffer.clear();
+                    self.state = State::Entity;
+                }
+                _ => self.emit(byte, text),
+            },
+            State::TagOpen => {
+                self.buffer.clear();
+                match byte {
+                    b'/' => self.state = State::TagName { end: true },
+                    b'!' => self.state = State::Declaration,
+                    b'?' => self.state = State::Skip,
+                    _ if byte.is_ascii_alphabetic() => {
+                        self.state = State::TagName { end: false };
+                        self.step(byte, text);
+                    }
+                    // Not a tag after all, as in "a < b"
+                    _ => {
+                        self.emit(b'<', text);
+                        self.state = State::Text;
+                        self.step(byte, text);
+                    }
+                }
+            }
+            State::TagName { end } => match byte {
+                b'>' => self.end_tag(end, false),
+                b'/' => {
+                    self.state = State::Attributes {
+                        end,
+                        quote: None,
+                        slash: true,
+                    }
+                }
+                _ if byte.is_ascii_whitespace() => {
+                    self.state = State::Attributes {
+                        end,
+                        quote: None,
+                        slash: false,
+                    }
+                }
+                _ if self.markup == Markup::Html => self.buffer.push(byte.to_ascii_lowercase()),
+                _ => self.buffer.push(byte),
+            },
+            State::Attributes {
+                end,
+                quote: None,
+                slash,
+            } => match byte {
+                b'>' => self.end_tag(end, slash),
+                b'"' | b'\'' => {
+                    self.state = State::Attributes {
+                        end,
+                        quote: Some(byte),
+                        slash: false,
+                    }
+                }
+                _ if byte.is_ascii_whitespace() => {}
+                _ => {
+                    self.state = State::Attributes {
+                        end,
+                        quote: None,
+                        slash: byte == b'/',
+                    }
+                }
+            },
+            State::Attributes {
+                end,
+                quote: Some(quote),
+                ..
+            } => {
+                if byte == quote {
+                    self.state = State::Attributes {
+                        end,
+                        quote: None,
+                        slash: false,
+                    }
+                }
+            }
+            State::Declaration => {
+                self.buffer.push(byte);
+                if self.buffer == b"--" {
+                    self.state = State::Comment { dashes: 0 };
+                } else if self.buffer == b"[CDATA[" {
+                    self.state = State::CData { brackets: 0 };
+                } else if !(b"--".starts_with(&self.buffer) || b"[CDATA[".starts_with(&self.buffer))
+                {
+                    self.state = if byte == b'>' {
+                        State::Text
+                    } else {
+                        State::Skip
+                    };
+                }
+            }
+            State::Comment { dashes } => {
+                self.state = match byte {
+                    b'-' => State::Comment { dashes: dashes + 1 },
+                    b'>' if dashes >= 2 => State::Text,
+                    _ => State::Comment { dashes: 0 },
+                }
+            }
+            State::CData { brackets } => match byte {
+                b']' => {
+                    self.state = State::CData {
+                        brackets: brackets + 1,
+                    }
+                }
+                b'>' if brackets >= 2 => {
+                    self.emit_all(&b"]".repeat(brackets - 2), text);
+                    self.state = State::Text;
+                }
+                _ => {
+                    self.emit_all(&b"]".repeat(brackets), text);
+                    self.emit(byte, text);
+                    self.state = State::CData { brackets: 0 };
+                }
+            },
+            State::Skip => {
+                if byte == b'>' {
+                    self.state = State::Text;
+                }
+            }
+            State::Entity => match byte {
+                b';' => {
+                    self.state = State::Text;
+                    self.decode_entity(text);
+                }
+                _ if (byte.is_ascii_alphanumeric() || byte == b'#')
+                    && self.buffer.len() < MAX_ENTITY_LEN =>
+                {
+                    self.buffer.push(byte);
+                }
+                // Not an entity after all, as in "Q&A"
+                _ => {
+                    self.state = State::Text;
+                    self.emit(b'&', text);
+                    let name = std::mem::take(&mut self.buffer);
+                    self.emit_all(&name, text);
+                    self.step(byte, text);
+                }
+            },
+            State::RawText { matched } => {
+                let name = self.stack.last().map_or(&[][..], String::as_bytes);
+                let expected = match matched {
+                    0 => Some(b'<'),
+                    1 => Some(b'/'),
+                    _ => name.get(matched - 2).copied(),
+                };
+                self.state = if expected != Some(byte.to_ascii_lowercase()) {
+                    State::RawText {
+                        matched: usize::from(byte == b'<'),
+                    }
+                } else if matched + 1 < name.len() + 2 {
+                    State::RawText {
+                        matched: matched + 1,
+                    }
+                } else {
+                    self.buffer = name.to_vec();
+                    State::Attributes {
+                        end: true,
+                        quote: None,
+                        slash: false,
+                    }
+                };
+            }
+        }
+    }
+
+    /// The input has ended in the middle of whatever it was in
+    fn finish(&mut self, text: &mut Vec<u8>) {
+        match self.state {
+            State::TagOpen => self.emit(b'<', text),
+            State::Entity => {
+                self.emit(b'&', text);
+                let name = std::mem::take(&mut self.buffer);
+                self.emit_all(&name, text);
+            }
+            State::CData { brackets } => self.emit_all(&b"]".repeat(brackets), text),
+            _ => {}
+        }
+        self.state = State::Text;
+        if self.pending == Some(b'\n') {
+            text.push(b'\n');
+        }
+        self.pending = None;
+    }
+
+    fn decode_entity(&mut self, text: &mut Vec<u8>) {
+        let name = std::mem::take(&mut self.buffer);
+        let decoded = std::str::from_utf8(&name).ok().and_then(|name| {
+            if let Some(number) = name.strip_prefix('#') {
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => number.parse(),
+                };
+                // Like browsers, replace invalid code points and U+0000
+                code.ok().map(|code| {
+                    char::from_u32(code)
+                        .filter(|&c| c != '\0')
+                        .unwrap_or(char::REPLACEMENT_CHARACTER)
+                })
+            } else {
+                ENTITIES
+                    .iter()
+                    .find(|(entity, _)| *entity == name)
+                    .map(|&(_, c)| c)
+            }
+        });
+        match decoded {
+            Some(c) => self.emit_all(c.encode_utf8(&mut [0; 4]).as_bytes(), text),
+            // Unknown entities are left as they are
+            None => {
+                self.emit(b'&', text);
+                self.emit_all(&name, text);
+                self.emit(b';', text);
+            }
+        }
+    }
+
+    /// A tag ends with `>`
+    fn end_tag(&mut self, end: bool, self_closing: bool) {
+        self.state = State::Text;
+        let name = String::from_utf8_lossy(&self.buffer).into_owned();
+        if name.is_empty() {
+            return;
+        }
+        let html = self.markup == Markup::Html;
+        let separator = match self.markup {
+            Markup::Html if BLOCK_ELEMENTS.contains(&name.as_str()) => Some(b'\n'),
+            Markup::Html if matches!(name.as_str(), "td" | "th") => Some(b' '),
+            Markup::Html => None,
+            Markup::Xml => Some(b' '),
+        };
+        if let Some(separator) = separator {
+            self.separate(separator);
+        }
+        if end {
+            if let Some(open) = self.stack.iter().rposition(|open| *open == name) {
+                self.stack.truncate(open);
+            }
+            return;
+        }
+        if html {
+            // Like `<li>` after `<li>`, or a block after `<p>`
+            let closes = |open: &str| {
+                (open == name && OPTIONAL_END.contains(&open))
+                    || (open == "p" && separator == Some(b'\n'))
+            };
+            if self.stack.last().is_some_and(|open| closes(open)) {
+                self.stack.pop();
+            }
+        }
+        self.elements
+            .counts
+            .entry(name.clone())
+            .or_default()
+            .elements += 1;
+        if self_closing || (html && VOID_ELEMENTS.contains(&name.as_str())) {
+            return;
+        }
+        if html && RAW_TEXT_ELEMENTS.contains(&name.as_str()) {
+            self.state = State::RawText { matched: 0 };
+        }
+        self.stack.push(name);
+    }
+}
+
+/// The text of HTML or XML markup, read from the markup as it's read
+///
+/// Leaves out tags with their attributes, comments, declarations like
+/// `<!DOCTYPE html>`, processing instructions, and the content of
+/// `<script>` and `<style>` elements, and decodes entities like `&amp;`
+/// or `&#8217;`. The text of CDATA sections is kept as it is. Line breaks
+/// go between block elements like paragraphs, and spaces between table
+/// cells or any XML elements, unless there already is whitespace.
+///
+/// The markup is expected in UTF-8 or another encoding compatible with
+/// ASCII, and entities are decoded to UTF-8.
+#[derive(Debug)]
+pub struct MarkupText<R> {
+    input: R,
+    tokenizer: Tokenizer,
+    buffer: Vec<u8>,
+    /// Text from the markup read so far, of which `start..` is left
+    text: Vec<u8>,
+    start: usize,
+    eof: bool,
+}
+
+impl<R: Read> MarkupText<R> {
+    pub fn new(input: R, markup: Markup) -> Self {
+        Self {
+            input,
+            tokenizer: Tokenizer::new(markup),
+            buffer: vec![0; BUF_SIZE],
+            text: Vec::new(),
+            start: 0,
+            eof: false,
+        }
+    }
+
+    /// Counts of the elements read so far
+    pub const fn elements(&self) -> &Elements {
+        &self.tokenizer.elements
+    }
+
+    pub fn into_elements(self) -> Elements {
+        self.tokenizer.elements
+    }
+}
+
+impl<R: Read> Read for MarkupText<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.start == self.text.len() {
+            if self.eof {
+                return Ok(0);
+            }
+            self.text.clear();
+            self.start = 0;
+            let bytes_read = match self.input.read(&mut self.buffer) {
+                Ok(n) => n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if bytes_read == 0 {
+                self.tokenizer.finish(&mut self.text);
+                self.eof = true;
+            }
+            for &byte in &self.buffer[..bytes_read] {
+                self.tokenizer.step(byte, &mut self.text);
+            }
+        }
+        let len = buf.len().min(self.text.len() - self.start);
+        buf[..len].copy_from_slice(&self.text[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Trickle;
+
+    fn extract(markup: &str, kind: Markup) -> (String, Elements) {
+        let mut reader = MarkupText::new(markup.as_bytes(), kind);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+
+        let mut trickled = MarkupText::new(Trickle(markup.as_bytes()), kind);
+        let mut same = String::new();
+        trickled.read_to_string(&mut same).unwrap();
+        assert_eq!(same, text, "{markup:?} byte by byte");
+        (text, reader.into_elements())
+    }
+
+    fn html(markup: &str) -> String {
+        extract(markup, Markup::Html).0
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            html("<!DOCTYPE html><html><head><title>Hi</title><style>p { color: red }</style></head>\
+                  <body><p class=\"a > b\">One <b>two</b>&nbsp;three<p>Four<br>five</p>\
+                  <script>if (a < b) { document.write('</p>') }</script></body></html>"),
+            "Hi\nOne two\u{A0}three\nFour\nfive\n"
+        );
+        assert_eq!(
+            html("<table><tr><td>a</td><td>b</td></tr></table>"),
+            "a b\n"
+        );
+        assert_eq!(html("<SCRIPT>x</Script >y"), "y");
+    }
+
+    #[test]
+    fn test_entities() {
+        assert_eq!(
+            html("&lt;&#65;&#x42;&rsquo;&#0;&unknown; Q&A &amp"),
+            "<AB’\u{FFFD}&unknown; Q&A &amp"
+        );
+    }
+
+    #[test]
+    fn test_tolerance() {
+        assert_eq!(html("a < b <!-- c -- > --> d"), "a < b  d");
+        assert_eq!(html("<?xml version=\"1.0\"?><![CDATA[<x>]]]>"), "<x>]");
+        assert_eq!(html("</><p"), "");
+        assert_eq!(html("<"), "<");
+    }
+
+    #[test]
+    fn test_xml() {
+        let (text, elements) = extract(
+            "<book><title>Rust</title><author>A</author><Note/><note>two words</note></book>",
+            Markup::Xml,
+        );
+        assert_eq!(text, "Rust A two words");
+        assert_eq!(
+            elements.get("book"),
+            ElementCounts {
+                elements: 1,
+                words: 4
+            }
+        );
+        assert_eq!(elements.get("Note").elements, 1);
+        assert_eq!(elements.get("note").words, 2);
+        assert_eq!(elements.len(), 5);
+    }
+
+    #[test]
+    fn test_elements() {
+        let (_, elements) = extract(
+            "<div><p>One <em>two</em></p><p>three <div>four</div></div>",
+            Markup::Html,
+        );
+        assert_eq!(
+            elements.get("p"),
+            ElementCounts {
+                elements: 2,
+                words: 3
+            }
+        );
+        assert_eq!(elements.get("div").words, 4);
+        assert_eq!(elements.get("em").words, 1);
+
+        let mut total = elements.clone();
+        total += elements;
+        assert_eq!(total.get("p").elements, 4);
+    }
+}
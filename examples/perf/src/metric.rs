@@ -0,0 +1,505 @@
+//! Metrics a counting pass computes
+//!
+//! A counting pass reads the input chunk by chunk and hands every chunk to
+//! each [`Metric`] in turn. Words, lines, characters and so on are all
+//! metrics, and custom ones can be counted alongside them with
+//! [`counter_with_metrics`](crate::counter_with_metrics).
+//!
+//! Metrics counted on consecutive pieces of the same input (for example
+//! on different threads) can be [merged](Metric::merge), which gives the
+//! same result as counting the whole input in one go.
+
+use unicode_width::UnicodeWidthChar;
+
+use crate::segments::{Segmentation, Segments};
+
+/// A chunk of input
+#[derive(Debug, Clone, Copy)]
+pub struct Chunk<'a> {
+    /// Raw input bytes
+    pub raw: &'a [u8],
+    /// The same chunk in an ASCII-compatible encoding, so newlines and
+    /// ASCII whitespace can be found without decoding: the raw bytes
+    /// themselves, or the decoded text for encodings like UTF-16
+    pub bytes: &'a [u8],
+    /// Decoded text, with U+FFFD in place of invalid sequences.
+    /// Only available if some metric [needs it](Metric::needs_text).
+    /// Multi-byte characters are never split across chunks.
+    pub text: Option<&'a str>,
+}
+
+impl<'a> Chunk<'a> {
+    /// A chunk of UTF-8 text
+    pub fn from_text(text: &'a str) -> Self {
+        Self {
+            raw: text.as_bytes(),
+            bytes: text.as_bytes(),
+            text: Some(text),
+        }
+    }
+}
+
+/// Something to count in the input
+pub trait Metric {
+    /// Whether this metric looks at [`Chunk::text`].
+    /// The input is only decoded if some metric does.
+    fn needs_text(&self) -> bool {
+        true
+    }
+
+    /// Count the next chunk of input
+    fn update(&mut self, chunk: &Chunk<'_>);
+
+    /// The input has ended
+    fn finish(&mut self) {}
+
+    /// Continue with the metric counted on the input that directly follows.
+    /// Neither of the two is finished yet.
+    fn merge(&mut self, next: Self)
+    where
+        Self: Sized;
+}
+
+/// Number of raw input bytes
+#[derive(Debug, Default, Clone)]
+pub struct Bytes {
+    count: usize,
+}
+
+impl Bytes {
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Metric for Bytes {
+    fn needs_text(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        self.count += chunk.raw.len();
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.count += next.count;
+    }
+}
+
+/// Number of lines.
+/// Like `str::lines`, a final line without a trailing newline still counts.
+#[derive(Debug, Default, Clone)]
+pub struct Lines {
+    count: usize,
+    /// The last byte seen so far was a newline (or there was none yet)
+    at_line_start: bool,
+    /// Whether any input was seen at all
+    seen: bool,
+}
+
+impl Lines {
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Like [`Metric::update`], with the newlines in the chunk already
+    /// counted, e.g. by [`Words::update_counting_lines`]
+    ///
+    /// [`Words::update_counting_lines`]: crate::Words::update_counting_lines
+    pub fn update_counted(&mut self, chunk: &Chunk<'_>, newlines: usize) {
+        let Some(&last) = chunk.bytes.last() else {
+            return;
+        };
+        self.count += newlines;
+        self.at_line_start = last == b'\n';
+        self.seen = true;
+    }
+}
+
+impl Metric for Lines {
+    fn needs_text(&self) -> bool {
+        false
+    }
+
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        self.update_counted(chunk, count_newlines(chunk.bytes));
+    }
+
+    fn finish(&mut self) {
+        if self.seen && !self.at_line_start {
+            self.count += 1;
+            self.at_line_start = true;
+        }
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.count += next.count;
+        if next.seen {
+            self.at_line_start = next.at_line_start;
+            self.seen = true;
+        }
+    }
+}
+
+/// Number of newlines in `bytes`.
+/// Summing each block into a `u8` lets the compiler vectorize the loop.
+fn count_newlines(bytes: &[u8]) -> usize {
+    bytes
+        .chunks(u8::MAX.into())
+        .map(|block| {
+            let newlines: u8 = block.iter().map(|&byte| u8::from(byte == b'\n')).sum();
+            usize::from(newlines)
+        })
+        .sum()
+}
+
+/// Number of Unicode scalar values
+#[derive(Debug, Default, Clone)]
+pub struct Chars {
+    count: usize,
+}
+
+impl Chars {
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Metric for Chars {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        // Fast path: in ASCII, every byte is a character
+        self.count += if text.is_ascii() {
+            text.len()
+        } else {
+            text.chars().count()
+        };
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.count += next.count;
+    }
+}
+
+/// Number of extended grapheme clusters, i.e. user-perceived characters
+#[derive(Debug, Clone)]
+pub struct Graphemes {
+    segments: Segments,
+}
+
+impl Default for Graphemes {
+    fn default() -> Self {
+        Self {
+            segments: Segments::new(Segmentation::Graphemes),
+        }
+    }
+}
+
+impl Graphemes {
+    /// Clusters counted so far. The last few are only counted once the
+    /// metric is finished.
+    pub const fn count(&self) -> usize {
+        self.segments.count()
+    }
+}
+
+impl Metric for Graphemes {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        if let Some(text) = chunk.text {
+            self.segments.update(text);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.segments.finish();
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.segments.merge(next.segments);
+    }
+}
+
+/// Width of a piece of a line, depending on the column it starts at.
+///
+/// A tab advances to the next tab stop, so everything after the first tab
+/// only depends on which tab stop that is, and all tab stops look alike.
+#[derive(Debug, Default, Clone, Copy)]
+struct Span {
+    /// Width before the first tab
+    before_tab: usize,
+    /// Width from the tab stop after the first tab, if there is one
+    after_tab: Option<usize>,
+}
+
+impl Span {
+    /// The column this span ends at when it starts at `column`
+    const fn end(self, column: usize, tab_width: usize) -> usize {
+        let column = column + self.before_tab;
+        match self.after_tab {
+            Some(after_tab) => (column / tab_width + 1) * tab_width + after_tab,
+            None => column,
+        }
+    }
+
+    fn push(&mut self, c: char, tab_width: usize) {
+        match (c, &mut self.after_tab) {
+            ('\t', Some(column)) => *column = (*column / tab_width + 1) * tab_width,
+            ('\t', None) => self.after_tab = Some(0),
+            (_, Some(column)) => *column += width(c),
+            (_, None) => self.before_tab += width(c),
+        }
+    }
+
+    /// This span followed by `next`
+    const fn then(self, next: Self, tab_width: usize) -> Self {
+        match self.after_tab {
+            Some(after_tab) => Self {
+                before_tab: self.before_tab,
+                after_tab: Some(next.end(after_tab, tab_width)),
+            },
+            None => Self {
+                before_tab: self.before_tab + next.before_tab,
+                after_tab: next.after_tab,
+            },
+        }
+    }
+}
+
+/// Display width of a character other than a tab or newline.
+///
+/// Combining marks take up no space and East Asian wide characters take up
+/// two columns. Control characters are not displayed, so they don't count
+/// either.
+fn width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Display width of the longest line (`wc -L`), along with its line number
+#[derive(Debug, Clone)]
+pub struct MaxLineWidth {
+    /// Distance between tab stops
+    tab_width: usize,
+    /// The first line, up to the first newline.
+    /// Its width depends on where the text before left off.
+    first: Span,
+    /// Number of newlines seen
+    newlines: usize,
+    /// Width and number of the first of the widest lines so far, where the
+    /// first line is line 1.
+    /// Only complete lines count, except for the first line.
+    widest: Option<(usize, usize)>,
+    /// Width of the line after the last newline
+    column: usize,
+    /// The last character seen so far was a newline (or there was none yet)
+    at_line_start: bool,
+    /// Whether any input was seen at all
+    seen: bool,
+}
+
+impl Default for MaxLineWidth {
+    fn default() -> Self {
+        Self::new(8)
+    }
+}
+
+impl MaxLineWidth {
+    pub fn new(tab_width: usize) -> Self {
+        Self {
+            tab_width: tab_width.max(1),
+            first: Span::default(),
+            newlines: 0,
+            widest: None,
+            column: 0,
+            at_line_start: true,
+            seen: false,
+        }
+    }
+
+    /// Display width of the longest line, in terminal columns
+    pub fn width(&self) -> usize {
+        self.widest.map_or(0, |(width, _)| width)
+    }
+
+    /// 1-based number of the longest line, or 0 if there are no lines
+    pub fn line(&self) -> usize {
+        self.widest.map_or(0, |(_, line)| line)
+    }
+
+    /// The column a line ends at when it goes on from `column`
+    fn measure(&self, column: usize, line: &str) -> usize {
+        line.chars().fold(column, |column, c| match c {
+            '\t' => (column / self.tab_width + 1) * self.tab_width,
+            _ => column + width(c),
+        })
+    }
+
+    fn end_line(&mut self, width: usize, line: usize) {
+        if self.widest.is_none_or(|(widest, _)| width > widest) {
+            self.widest = Some((width, line));
+        }
+    }
+}
+
+impl Metric for MaxLineWidth {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text.filter(|text| !text.is_empty()) else {
+            return;
+        };
+        let mut lines = text.split('\n');
+        // The current line goes on
+        let current = lines.next().unwrap_or_default();
+        if self.newlines == 0 {
+            for c in current.chars() {
+                self.first.push(c, self.tab_width);
+            }
+        } else {
+            self.column = self.measure(self.column, current);
+        }
+        // Every newline ends the current line and starts a new one
+        for line in lines {
+            if self.newlines > 0 {
+                self.end_line(self.column, self.newlines + 1);
+            }
+            self.newlines += 1;
+            self.column = self.measure(0, line);
+        }
+        self.at_line_start = text.ends_with('\n');
+        self.seen = true;
+    }
+
+    fn finish(&mut self) {
+        if !self.seen {
+            return;
+        }
+        // The first line can only be measured now that nothing comes before
+        let first = (self.first.end(0, self.tab_width), 1);
+        let rest = self.widest.take();
+        self.end_line(first.0, first.1);
+        if let Some((width, line)) = rest {
+            self.end_line(width, line);
+        }
+        if self.newlines > 0 && !self.at_line_start {
+            self.end_line(self.column, self.newlines + 1);
+        }
+        self.first = Span::default();
+        self.seen = false;
+    }
+
+    fn merge(&mut self, next: Self) {
+        if !next.seen {
+            return;
+        }
+        if next.newlines == 0 {
+            // The current line goes on
+            if self.newlines == 0 {
+                self.first = self.first.then(next.first, self.tab_width);
+            } else {
+                self.column = next.first.end(self.column, self.tab_width);
+            }
+        } else {
+            if self.newlines == 0 {
+                self.first = self.first.then(next.first, self.tab_width);
+            } else {
+                let width = next.first.end(self.column, self.tab_width);
+                self.end_line(width, self.newlines + 1);
+            }
+            if let Some((width, line)) = next.widest {
+                self.end_line(width, line + self.newlines);
+            }
+            self.newlines += next.newlines;
+            self.column = next.column;
+        }
+        self.at_line_start = next.at_line_start;
+        self.seen = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{WordBoundary, Words};
+
+    const TEXT: &str = "naïve cafe\u{301}\tdon't 🇩🇪🇫🇷\r\n\n\t日本語 a.b\twide\tline\nend";
+
+    /// Count `TEXT` split into pieces at `splits`, each on its own, then
+    /// merge them and compare with counting it in one go
+    fn assert_mergeable<M: Metric + std::fmt::Debug>(
+        new: impl Fn() -> M,
+        result: impl Fn(&M) -> (usize, usize),
+    ) {
+        let mut whole = new();
+        whole.update(&Chunk::from_text(TEXT));
+        whole.finish();
+        let expected = result(&whole);
+
+        let boundaries: Vec<usize> = TEXT.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first).step_by(7) {
+                let mut merged = new();
+                for piece in [&TEXT[..first], &TEXT[first..second], &TEXT[second..]] {
+                    let mut metric = new();
+                    metric.update(&Chunk::from_text(piece));
+                    merged.merge(metric);
+                }
+                merged.finish();
+                assert_eq!(result(&merged), expected, "split at {first} and {second}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_bytes_lines_chars() {
+        assert_mergeable(Bytes::default, |m| (m.count(), 0));
+        assert_mergeable(Lines::default, |m| (m.count(), 0));
+        assert_mergeable(Chars::default, |m| (m.count(), 0));
+    }
+
+    #[test]
+    fn test_merge_graphemes() {
+        assert_mergeable(Graphemes::default, |m| (m.count(), 0));
+    }
+
+    #[test]
+    fn test_merge_words() {
+        for boundary in [
+            WordBoundary::Ascii,
+            WordBoundary::Unicode,
+            WordBoundary::Uax29,
+        ] {
+            assert_mergeable(|| Words::new(boundary), |m| (m.count(), 0));
+        }
+    }
+
+    #[test]
+    fn test_lines_counted_by_words() {
+        let chunk = Chunk::from_text(TEXT);
+        let mut words = Words::new(WordBoundary::Ascii);
+        let mut lines = Lines::default();
+        let newlines = words.update_counting_lines(&chunk).unwrap();
+        lines.update_counted(&chunk, newlines);
+        lines.finish();
+        assert_eq!(lines.count(), 4);
+        assert_eq!(
+            Words::new(WordBoundary::Unicode).update_counting_lines(&chunk),
+            None
+        );
+    }
+
+    #[test]
+    fn test_merge_max_line_width() {
+        for tab_width in [1, 3, 8] {
+            assert_mergeable(|| MaxLineWidth::new(tab_width), |m| (m.width(), m.line()));
+        }
+    }
+
+    #[test]
+    fn test_max_line_width_tabs() {
+        let mut metric = MaxLineWidth::new(4);
+        metric.update(&Chunk::from_text("ab\tc\t\td\nx"));
+        metric.finish();
+        // 2 columns, tab to 4, 1 column, tabs to 8 and 12, 1 column
+        assert_eq!((metric.width(), metric.line()), (13, 1));
+    }
+}
@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use crate::metric::{Chunk, Metric};
+use crate::segments;
+use crate::WordBoundary;
+
+/// How often each sequence of `n` consecutive words occurs
+///
+/// Words are told apart the same way [`Frequencies`](crate::Frequencies)
+/// does, except that UAX #29 words never span whitespace. An n-gram is
+/// written as its words separated by single spaces, e.g. "of the".
+///
+/// Besides the n-grams, only the first and last `n - 1` words of the text
+/// seen so far are kept, so n-grams that cross into text counted
+/// separately can be added once it's merged.
+#[derive(Debug, Clone)]
+pub struct NGrams {
+    n: usize,
+    boundary: WordBoundary,
+    /// Count upper- and lowercase spellings of a word as the same word
+    ignore_case: bool,
+    counts: HashMap<String, usize>,
+    /// Text before the first separator, which might continue a word from
+    /// the text before
+    head: String,
+    /// Whether there was a separator yet, so `tail` comes after `head`
+    split: bool,
+    /// Text after the last separator, which the next chunk might continue
+    tail: String,
+    /// First words after `head`, up to `n - 1` of them
+    first: Vec<String>,
+    /// Last words before `tail`, up to `n - 1` of them
+    last: Vec<String>,
+}
+
+impl NGrams {
+    /// # Panics
+    ///
+    /// If `n` is 0
+    pub fn new(n: usize, boundary: WordBoundary, ignore_case: bool) -> Self {
+        assert!(n > 0, "n-grams need at least one word");
+        Self {
+            n,
+            boundary,
+            ignore_case,
+            counts: HashMap::new(),
+            head: String::new(),
+            split: false,
+            tail: String::new(),
+            first: Vec::new(),
+            last: Vec::new(),
+        }
+    }
+
+    /// How often the n-gram occurs, given as words separated by spaces
+    pub fn get(&self, ngram: &str) -> usize {
+        let ngram = if self.ignore_case {
+            ngram.to_lowercase()
+        } else {
+            ngram.to_string()
+        };
+        self.counts.get(&ngram).copied().unwrap_or(0)
+    }
+
+    /// Number of distinct n-grams
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// All n-grams that occur at least `min_count` times, most frequent
+    /// first.
+    /// N-grams that occur equally often are sorted alphabetically.
+    pub fn sorted(&self, min_count: usize) -> Vec<(&str, usize)> {
+        let mut sorted: Vec<_> = self
+            .counts
+            .iter()
+            .filter(|(_, &count)| count >= min_count)
+            .map(|(ngram, &count)| (ngram.as_str(), count))
+            .collect();
+        sorted.sort_unstable_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        sorted
+    }
+
+    /// Words of `text`, which has no separators in it
+    fn words<'t>(&self, text: &'t str) -> Vec<&'t str> {
+        match self.boundary {
+            WordBoundary::Ascii | WordBoundary::Unicode if text.is_empty() => Vec::new(),
+            WordBoundary::Ascii | WordBoundary::Unicode => vec![text],
+            WordBoundary::Uax29 => segments::words(text).collect(),
+        }
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        if self.ignore_case {
+            word.to_lowercase()
+        } else {
+            word.to_string()
+        }
+    }
+
+    /// Add the words of `text` after the ones seen so far
+    fn push(&mut self, text: &str) {
+        for word in self.words(text) {
+            let word = self.normalize(word);
+            if self.last.len() == self.n - 1 {
+                let mut ngram = self.last.join(" ");
+                if !ngram.is_empty() {
+                    ngram.push(' ');
+                }
+                ngram.push_str(&word);
+                *self.counts.entry(ngram).or_default() += 1;
+            }
+            if self.first.len() < self.n - 1 {
+                self.first.push(word.clone());
+            }
+            self.last.push(word);
+            if self.last.len() >= self.n {
+                self.last.remove(0);
+            }
+        }
+    }
+
+    /// Add the words made up of the tail and `rest`
+    fn end_word(&mut self, rest: &str) {
+        let mut text = std::mem::take(&mut self.tail);
+        text.push_str(rest);
+        self.push(&text);
+        text.clear();
+        self.tail = text;
+    }
+
+    /// Count the n-grams in `words` that span more than the first or last
+    /// `n - 1` of them
+    fn count_window(&mut self, words: &[String]) {
+        for ngram in words.windows(self.n) {
+            *self.counts.entry(ngram.join(" ")).or_default() += 1;
+        }
+    }
+}
+
+impl Metric for NGrams {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        let is_separator = match self.boundary {
+            WordBoundary::Ascii => |c: char| c.is_ascii_whitespace(),
+            WordBoundary::Unicode | WordBoundary::Uax29 => char::is_whitespace,
+        };
+
+        let mut runs = text.split(is_separator);
+        let first = runs.next().unwrap_or_default();
+        let Some(last) = runs.next_back() else {
+            // The current word goes on
+            if self.split {
+                self.tail.push_str(first);
+            } else {
+                self.head.push_str(first);
+            }
+            return;
+        };
+        if self.split {
+            self.end_word(first);
+        } else {
+            self.head.push_str(first);
+            self.split = true;
+        }
+        for run in runs {
+            self.push(run);
+        }
+        self.tail.push_str(last);
+    }
+
+    fn finish(&mut self) {
+        // The input starts right before the head and ends right after the
+        // tail, which is the same as merging with empty text on both sides
+        let mut start = Self::new(self.n, self.boundary, self.ignore_case);
+        start.split = true;
+        let end = start.clone();
+        start.merge(std::mem::replace(self, end.clone()));
+        start.merge(end);
+        *self = start;
+    }
+
+    fn merge(&mut self, next: Self) {
+        if !next.split {
+            if self.split {
+                self.tail.push_str(&next.head);
+            } else {
+                self.head.push_str(&next.head);
+            }
+            return;
+        }
+        if !self.split {
+            let mut head = std::mem::take(&mut self.head);
+            head.push_str(&next.head);
+            *self = Self { head, ..next };
+            return;
+        }
+
+        // Words between the last ones of this text and the first ones of
+        // the next
+        let mut between = std::mem::take(&mut self.tail);
+        between.push_str(&next.head);
+        let between: Vec<String> = self
+            .words(&between)
+            .into_iter()
+            .map(|word| self.normalize(word))
+            .collect();
+
+        let mut window = std::mem::take(&mut self.last);
+        window.extend(between.iter().cloned());
+        let joined = window.len();
+        window.extend(next.first.iter().cloned());
+        self.count_window(&window);
+
+        // The first words only change if there were fewer than `n - 1`
+        let missing = (self.n - 1).saturating_sub(self.first.len());
+        self.first.extend(
+            window[joined - between.len()..]
+                .iter()
+                .take(missing)
+                .cloned(),
+        );
+
+        window.truncate(joined);
+        window.extend(next.last);
+        let keep = window.len().saturating_sub(self.n - 1);
+        self.last = window.split_off(keep);
+
+        for (ngram, count) in next.counts {
+            *self.counts.entry(ngram).or_default() += count;
+        }
+        self.tail = next.tail;
+    }
+}
+
+/// Add up the n-grams of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for NGrams {
+    fn add_assign(&mut self, other: Self) {
+        for (ngram, count) in other.counts {
+            *self.counts.entry(ngram).or_default() += count;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(pieces: &[&str], n: usize, boundary: WordBoundary) -> NGrams {
+        let mut merged = NGrams::new(n, boundary, false);
+        for piece in pieces {
+            let mut ngrams = NGrams::new(n, boundary, false);
+            ngrams.update(&Chunk::from_text(piece));
+            merged.merge(ngrams);
+        }
+        merged.finish();
+        merged
+    }
+
+    #[test]
+    fn test_bigrams() {
+        let text = "to be or not to be\nthat is the question";
+        let ngrams = count(&[text], 2, WordBoundary::Ascii);
+        assert_eq!(ngrams.sorted(2), [("to be", 2)]);
+        assert_eq!(ngrams.get("be that"), 1);
+        assert_eq!(ngrams.len(), 8);
+
+        let ngrams = count(&[text], 3, WordBoundary::Ascii);
+        assert_eq!(ngrams.sorted(1)[0], ("be or not", 1));
+        assert_eq!(ngrams.len(), 8);
+
+        let ngrams = count(&["one two"], 3, WordBoundary::Ascii);
+        assert!(ngrams.is_empty());
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let mut ngrams = NGrams::new(2, WordBoundary::Uax29, true);
+        ngrams.update(&Chunk::from_text("The cat. the Cat!"));
+        ngrams.finish();
+        assert_eq!(ngrams.get("THE CAT"), 2);
+        assert_eq!(ngrams.sorted(1), [("the cat", 2), ("cat the", 1)]);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "a b c don't\u{3000}stop a b, c a b c";
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for boundary in [
+            WordBoundary::Ascii,
+            WordBoundary::Unicode,
+            WordBoundary::Uax29,
+        ] {
+            for n in 1..=4 {
+                let expected = count(&[text], n, boundary).counts;
+                for &first in &boundaries {
+                    for &second in boundaries.iter().filter(|&&i| i >= first) {
+                        let pieces = [&text[..first], &text[first..second], &text[second..]];
+                        assert_eq!(
+                            count(&pieces, n, boundary).counts,
+                            expected,
+                            "{boundary:?} {n}-grams split at {first} and {second}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_total() {
+        let mut total = count(&["a b"], 2, WordBoundary::Ascii);
+        total += count(&["b c"], 2, WordBoundary::Ascii);
+        // N-grams never span separate inputs
+        assert_eq!(total.sorted(1), [("a b", 1), ("b c", 1)]);
+    }
+}
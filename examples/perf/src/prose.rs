@@ -0,0 +1,399 @@
+//! Sentences and paragraphs
+//!
+//! Both are counted by small state machines over characters. Their state
+//! only depends on the text before until a certain point, e.g. the first
+//! word after a space, so that's all a piece of input counted on its own
+//! needs to keep to be merged with the piece before it.
+
+use crate::metric::{Chunk, Metric};
+use crate::resume::{Resumable, Resume};
+
+/// Abbreviations that end with a period, but not a sentence
+///
+/// Words that often end a sentence on their own, like "no", "co", or "fig",
+/// aren't in here, even though they're abbreviations, too.
+const ABBREVIATIONS: &[&str] = &[
+    "mr", "mrs", "ms", "dr", "prof", "sr", "jr", "st", "mt", "vs", "e.g", "i.e", "cf", "etc",
+    "vol", "approx", "inc", "ltd",
+];
+
+/// Longest word that can still be an abbreviation
+const MAX_ABBREVIATION_LEN: usize = 8;
+
+/// Sentence detection
+///
+/// A sentence ends with a period, question mark, or exclamation mark
+/// followed by whitespace, possibly with closing quotes or brackets in
+/// between, or at a paragraph break. Periods after abbreviations like
+/// "Mr." or initials like "J." don't count, but "A." and "I." are words.
+/// Only text with letters or digits makes a sentence.
+#[derive(Debug, Clone)]
+struct SentenceMachine {
+    count: usize,
+    /// There are letters or digits since the end of the last sentence
+    in_sentence: bool,
+    /// The sentence ends with the next whitespace
+    pending_end: bool,
+    /// Start of the word so far, to look for abbreviations
+    word: [u8; MAX_ABBREVIATION_LEN],
+    /// Length of the word so far, or more than fits if it's longer than
+    /// any abbreviation
+    word_len: usize,
+    /// The current line has no text so far
+    line_blank: bool,
+    /// There was whitespace, so the current word is known
+    after_space: bool,
+    synced: bool,
+}
+
+impl Default for SentenceMachine {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            in_sentence: false,
+            pending_end: false,
+            word: [0; MAX_ABBREVIATION_LEN],
+            word_len: 0,
+            line_blank: true,
+            after_space: false,
+            synced: false,
+        }
+    }
+}
+
+impl SentenceMachine {
+    fn end_sentence(&mut self) {
+        if self.in_sentence {
+            self.count += 1;
+        }
+        self.in_sentence = false;
+        self.pending_end = false;
+    }
+
+    /// Letters or digits, which start or continue a sentence
+    fn alphanumeric(&mut self, bytes: &[u8]) {
+        self.line_blank = false;
+        self.pending_end = false;
+        self.in_sentence = true;
+        self.synced |= self.after_space;
+        self.push(bytes);
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        let len = self.word_len + bytes.len();
+        if let Some(word) = self.word.get_mut(self.word_len..len) {
+            word.copy_from_slice(bytes);
+        }
+        self.word_len = len;
+    }
+
+    /// Whether the current word is an abbreviation or an initial
+    fn is_abbreviation(&self) -> bool {
+        let Some(word) = self.word.get(..self.word_len) else {
+            return false;
+        };
+        let word = std::str::from_utf8(word)
+            .unwrap_or_default()
+            .trim_start_matches(|c: char| !c.is_alphanumeric());
+        let mut chars = word.chars();
+        let initial = matches!(
+            (chars.next(), chars.next()),
+            (Some(c), None) if c.is_uppercase() && c != 'A' && c != 'I'
+        );
+        initial || ABBREVIATIONS.contains(&word.to_lowercase().as_str())
+    }
+
+    fn step(&mut self, c: char) {
+        if c.is_whitespace() {
+            if self.pending_end {
+                self.end_sentence();
+            }
+            if c == '\n' {
+                // A blank line ends a paragraph, and so a sentence
+                if self.line_blank {
+                    self.end_sentence();
+                }
+                self.line_blank = true;
+            }
+            self.word_len = 0;
+            self.after_space = true;
+            return;
+        }
+
+        self.line_blank = false;
+        match c {
+            '.' | '!' | '?' | '…' => {
+                if self.in_sentence && !(c == '.' && self.is_abbreviation()) {
+                    self.pending_end = true;
+                }
+            }
+            // Ideographic full stops need no space after them
+            '。' | '！' | '？' => self.end_sentence(),
+            '\'' | '"' | ')' | ']' | '}' | '’' | '”' | '»' => {}
+            _ if c.is_alphanumeric() => {
+                self.alphanumeric(c.encode_utf8(&mut [0; 4]).as_bytes());
+                return;
+            }
+            _ => self.pending_end = false,
+        }
+        self.push(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+}
+
+impl Resume for SentenceMachine {
+    /// Fast path: ASCII letters and digits, by far the most common
+    /// characters, need no decoding
+    fn update(&mut self, text: &str) {
+        let mut chars = text.chars();
+        while let Some(&byte) = chars.as_str().as_bytes().first() {
+            if byte.is_ascii_alphanumeric() {
+                self.alphanumeric(&[byte]);
+                chars.next();
+            } else if let Some(c) = chars.next() {
+                self.step(c);
+            }
+        }
+    }
+
+    fn update_head(&mut self, text: &str) -> Option<usize> {
+        step_until(text, |c| {
+            self.step(c);
+            self.synced
+        })
+    }
+
+    fn resume(&mut self, next: Self, end: &Self) {
+        *self = Self {
+            count: self.count + next.count - end.count,
+            ..next
+        };
+    }
+
+    /// The last sentence needs no punctuation
+    fn finish(&mut self) {
+        self.end_sentence();
+    }
+}
+
+/// Paragraph detection: a paragraph is a run of lines with some text,
+/// separated from the next one by blank lines
+#[derive(Debug, Clone)]
+struct ParagraphMachine {
+    count: usize,
+    in_paragraph: bool,
+    /// The current line has no text so far
+    line_blank: bool,
+}
+
+impl Default for ParagraphMachine {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            in_paragraph: false,
+            line_blank: true,
+        }
+    }
+}
+
+impl ParagraphMachine {
+    /// Text within a line
+    fn content(&mut self, text: &str) {
+        if self.line_blank {
+            if let Some(c) = text.chars().find(|c| !c.is_whitespace()) {
+                self.step(c);
+            }
+        }
+    }
+
+    fn step(&mut self, c: char) {
+        if c == '\n' {
+            if self.line_blank {
+                self.in_paragraph = false;
+            }
+            self.line_blank = true;
+        } else if !c.is_whitespace() {
+            if !self.in_paragraph {
+                self.count += 1;
+                self.in_paragraph = true;
+            }
+            self.line_blank = false;
+        }
+    }
+}
+
+impl Resume for ParagraphMachine {
+    /// Fast path: only the first non-whitespace character of each line
+    /// matters
+    fn update(&mut self, text: &str) {
+        let mut lines = text.split('\n');
+        // The current line goes on
+        self.content(lines.next().unwrap_or_default());
+        for line in lines {
+            self.step('\n');
+            self.content(line);
+        }
+    }
+
+    /// Synced within a line of text
+    fn update_head(&mut self, text: &str) -> Option<usize> {
+        step_until(text, |c| {
+            self.step(c);
+            !self.line_blank
+        })
+    }
+
+    fn resume(&mut self, next: Self, end: &Self) {
+        *self = Self {
+            count: self.count + next.count - end.count,
+            ..next
+        };
+    }
+}
+
+/// Step through the characters of `text` until `step` returns whether the
+/// state no longer depends on the text before, and return where
+fn step_until(text: &str, mut step: impl FnMut(char) -> bool) -> Option<usize> {
+    text.char_indices()
+        .find_map(|(i, c)| step(c).then_some(i + c.len_utf8()))
+}
+
+/// Number of sentences
+#[derive(Debug, Default, Clone)]
+pub struct Sentences {
+    inner: Resumable<SentenceMachine>,
+    count: usize,
+}
+
+impl Sentences {
+    /// Sentences counted once the metric is finished
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Metric for Sentences {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        if let Some(text) = chunk.text {
+            self.inner.update(text);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+        self.count = self.inner.get().count;
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.inner.merge(next.inner);
+    }
+}
+
+/// Number of paragraphs
+#[derive(Debug, Default, Clone)]
+pub struct Paragraphs {
+    inner: Resumable<ParagraphMachine>,
+    count: usize,
+}
+
+impl Paragraphs {
+    /// Paragraphs counted once the metric is finished
+    pub const fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Metric for Paragraphs {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        if let Some(text) = chunk.text {
+            self.inner.update(text);
+        }
+    }
+
+    fn finish(&mut self) {
+        self.inner.finish();
+        self.count = self.inner.get().count;
+    }
+
+    fn merge(&mut self, next: Self) {
+        self.inner.merge(next.inner);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(text: &str) -> (usize, usize) {
+        let mut sentences = Sentences::default();
+        let mut paragraphs = Paragraphs::default();
+        sentences.update(&Chunk::from_text(text));
+        paragraphs.update(&Chunk::from_text(text));
+        sentences.finish();
+        paragraphs.finish();
+        (sentences.count(), paragraphs.count())
+    }
+
+    #[test]
+    fn test_sentences() {
+        assert_eq!(count("Mr. Smith went to Washington. He liked it!").0, 2);
+        assert_eq!(
+            count("Really?! (Yes.) \"Fine.\"  J. R. R. Tolkien wrote e.g. this").0,
+            4
+        );
+        assert_eq!(count("Pi is 3.14 or so... Right").0, 2);
+        // Words that are abbreviations, too, and single letters that aren't
+        // initials
+        assert_eq!(
+            count("He said no. Then he left. I was at the co. It is A. Really.").0,
+            5
+        );
+        assert_eq!(count("So do I. Then solve for x. Done").0, 3);
+        assert_eq!(count("... --- !!!").0, 0);
+        assert_eq!(count("你好。世界").0, 2);
+        // A heading without punctuation ends at the blank line after it
+        assert_eq!(count("CHAPTER I\r\n\r\nDown the Rabbit-Hole.").0, 2);
+    }
+
+    #[test]
+    fn test_paragraphs() {
+        assert_eq!(count(""), (0, 0));
+        assert_eq!(count("\n \n\t\n"), (0, 0));
+        assert_eq!(count("one\ntwo\n\n  three\n \nfour\n"), (3, 3));
+    }
+
+    #[test]
+    fn test_alice() {
+        let alice = std::fs::read_to_string("../../fixtures/alice.txt").unwrap();
+        let (sentences, paragraphs) = count(&alice);
+        assert_eq!(paragraphs, 827);
+        assert_eq!(sentences, 1694);
+    }
+
+    #[test]
+    fn test_merge() {
+        let text = "Mr. Smith\tsaid: \"Hi!\" \r\n\r\nIt's 3.14, e.g. pi.  \
+                    \nThe end.\n\n\n  Trailing words";
+        let expected = count(text);
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first) {
+                let mut sentences = Sentences::default();
+                let mut paragraphs = Paragraphs::default();
+                for piece in [&text[..first], &text[first..second], &text[second..]] {
+                    let (mut s, mut p) = (Sentences::default(), Paragraphs::default());
+                    s.update(&Chunk::from_text(piece));
+                    p.update(&Chunk::from_text(piece));
+                    sentences.merge(s);
+                    paragraphs.merge(p);
+                }
+                sentences.finish();
+                paragraphs.finish();
+                assert_eq!(
+                    (sentences.count(), paragraphs.count()),
+                    expected,
+                    "split at {first} and {second}"
+                );
+            }
+        }
+    }
+}
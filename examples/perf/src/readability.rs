@@ -0,0 +1,334 @@
+use crate::metric::{Chunk, Metric};
+
+/// Letters that stand for vowels, including accented ones in loanwords
+/// like "café" or "naïve"
+const VOWELS: &str = "aeiouyàáâäèéêëìíîïòóôöùúûü";
+
+/// Whether `c` can be part of a word, e.g. the letters of "don't"
+fn is_word_char(c: char) -> bool {
+    c.is_alphabetic() || c == '\'' || c == '’'
+}
+
+/// Estimate the number of syllables of an English word
+///
+/// Counts groups of vowels, then corrects for a silent final "e"
+/// ("make"), silent "-ed" and "-es" endings ("jumped", "makes"), and
+/// vowel pairs that are spoken separately ("piano").
+/// Like any such heuristic, it's wrong for some words, but close enough
+/// for readability scores. Every word has at least one syllable.
+pub fn syllables(word: &str) -> usize {
+    let word: Vec<char> = word
+        .chars()
+        .filter(|c| c.is_alphabetic())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if word.is_empty() {
+        return 0;
+    }
+    // A "y" at the start of a word is a consonant, as in "yes"
+    let is_vowel = |i: usize| VOWELS.contains(word[i]) && !(i == 0 && word[i] == 'y');
+    let is_any = |i: usize, letters: &str| letters.contains(word[i]);
+
+    let mut count = (0..word.len())
+        .filter(|&i| is_vowel(i) && (i == 0 || !is_vowel(i - 1)))
+        .count();
+
+    let len = word.len();
+    if len > 2 && word[len - 1] == 'e' && !is_vowel(len - 2) {
+        // "-le" after a consonant is a syllable of its own, as in "table"
+        let syllabic_le = len > 3 && word[len - 2] == 'l' && !is_vowel(len - 3);
+        if !syllabic_le {
+            count -= 1;
+        }
+    } else if len > 3 && word[len - 2] == 'e' && !is_vowel(len - 3) {
+        let sibilant = is_any(len - 3, "sxzcg") || (word[len - 3] == 'h' && is_any(len - 4, "cs"));
+        let silent = match word[len - 1] {
+            // Spoken after "t" and "d", as in "wanted"
+            'd' => !is_any(len - 3, "td"),
+            // Spoken after sibilants, as in "boxes" or "pages"
+            's' => !sibilant,
+            _ => false,
+        };
+        if silent {
+            count -= 1;
+        }
+    }
+
+    // "ia" and "io" are two syllables, except in endings like "-tion",
+    // "-sion", or "-cial"
+    count += (1..len - 1)
+        .filter(|&i| word[i] == 'i' && is_any(i + 1, "ao") && !is_any(i - 1, "ctsxg"))
+        .count();
+
+    count.max(1)
+}
+
+/// Scores of the common readability formulas for English text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scores {
+    /// From 0 (very hard) to 100 (very easy) and beyond
+    pub flesch_reading_ease: f64,
+    /// U.S. school grade level
+    pub flesch_kincaid_grade: f64,
+    /// Years of formal education needed to understand the text on a first
+    /// reading
+    pub gunning_fog: f64,
+    /// Simple Measure of Gobbledygook, a grade level.
+    /// Meant for samples of 30 sentences or more.
+    pub smog: f64,
+}
+
+/// Word and syllable counts readability formulas are based on
+///
+/// Words are runs of letters, so numbers don't count, and apostrophes
+/// inside words keep them together. Sentences are counted separately,
+/// see [`Sentences`](crate::Sentences), so the [`Scores`] take them as
+/// an argument.
+#[derive(Debug, Default, Clone)]
+pub struct Readability {
+    words: usize,
+    syllables: usize,
+    /// Words with three or more syllables
+    polysyllables: usize,
+    /// Polysyllables that don't only have three syllables because of an
+    /// "-ed", "-es", or "-ing" ending, as Gunning Fog defines them
+    complex_words: usize,
+    /// Text before the first separator, which might continue a word from
+    /// the text before
+    head: String,
+    /// Whether there was a separator yet, so `tail` comes after `head`
+    split: bool,
+    /// Text after the last separator, which the next chunk might continue
+    tail: String,
+}
+
+impl Readability {
+    pub const fn words(&self) -> usize {
+        self.words
+    }
+
+    pub const fn syllables(&self) -> usize {
+        self.syllables
+    }
+
+    pub const fn polysyllables(&self) -> usize {
+        self.polysyllables
+    }
+
+    /// Scores for text with the given number of sentences, or `None` if
+    /// there are no words or sentences to score
+    pub fn scores(&self, sentences: usize) -> Option<Scores> {
+        if self.words == 0 || sentences == 0 {
+            return None;
+        }
+        let words = self.words as f64;
+        let sentences = sentences as f64;
+        let words_per_sentence = words / sentences;
+        let syllables_per_word = self.syllables as f64 / words;
+        Some(Scores {
+            flesch_reading_ease: 84.6f64.mul_add(
+                -syllables_per_word,
+                1.015f64.mul_add(-words_per_sentence, 206.835),
+            ),
+            flesch_kincaid_grade: 0.39f64.mul_add(
+                words_per_sentence,
+                11.8f64.mul_add(syllables_per_word, -15.59),
+            ),
+            gunning_fog: 0.4
+                * 100.0f64.mul_add(self.complex_words as f64 / words, words_per_sentence),
+            smog: 1.043f64.mul_add(
+                (self.polysyllables as f64 * 30.0 / sentences).sqrt(),
+                3.1291,
+            ),
+        })
+    }
+
+    fn add(&mut self, word: &str) {
+        let word = word.trim_matches(|c: char| !c.is_alphabetic());
+        if word.is_empty() {
+            return;
+        }
+        let syllables = syllables(word);
+        self.words += 1;
+        self.syllables += syllables;
+        if syllables >= 3 {
+            self.polysyllables += 1;
+            let inflected = ["ed", "es", "ing"].iter().any(|ending| {
+                word.strip_suffix(ending)
+                    .is_some_and(|stem| self::syllables(stem) < 3)
+            });
+            if !inflected {
+                self.complex_words += 1;
+            }
+        }
+    }
+
+    /// Add the word made up of the tail and `rest`
+    fn end_word(&mut self, rest: &str) {
+        let mut word = std::mem::take(&mut self.tail);
+        word.push_str(rest);
+        self.add(&word);
+        word.clear();
+        self.tail = word;
+    }
+}
+
+impl Metric for Readability {
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        let Some(text) = chunk.text else {
+            return;
+        };
+        let mut words = text.split(|c| !is_word_char(c));
+        let first = words.next().unwrap_or_default();
+        let Some(last) = words.next_back() else {
+            // The current word goes on
+            if self.split {
+                self.tail.push_str(first);
+            } else {
+                self.head.push_str(first);
+            }
+            return;
+        };
+        if self.split {
+            self.end_word(first);
+        } else {
+            self.head.push_str(first);
+            self.split = true;
+        }
+        for word in words {
+            self.add(word);
+        }
+        self.tail.push_str(last);
+    }
+
+    fn finish(&mut self) {
+        let head = std::mem::take(&mut self.head);
+        self.add(&head);
+        self.end_word("");
+        self.split = true;
+    }
+
+    fn merge(&mut self, next: Self) {
+        if !next.split {
+            if self.split {
+                self.tail.push_str(&next.head);
+            } else {
+                self.head.push_str(&next.head);
+            }
+        } else {
+            if self.split {
+                self.end_word(&next.head);
+            } else {
+                self.head.push_str(&next.head);
+                self.split = true;
+            }
+            self.tail = next.tail;
+        }
+        self.words += next.words;
+        self.syllables += next.syllables;
+        self.polysyllables += next.polysyllables;
+        self.complex_words += next.complex_words;
+    }
+}
+
+/// Add up the counts of separate inputs, e.g. for a total over several
+/// files
+impl std::ops::AddAssign for Readability {
+    fn add_assign(&mut self, other: Self) {
+        self.words += other.words;
+        self.syllables += other.syllables;
+        self.polysyllables += other.polysyllables;
+        self.complex_words += other.complex_words;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(pieces: &[&str]) -> Readability {
+        let mut merged = Readability::default();
+        for piece in pieces {
+            let mut readability = Readability::default();
+            readability.update(&Chunk::from_text(piece));
+            merged.merge(readability);
+        }
+        merged.finish();
+        merged
+    }
+
+    #[test]
+    fn test_syllables() {
+        for (word, expected) in [
+            ("the", 1),
+            ("cat", 1),
+            ("yes", 1),
+            ("make", 1),
+            ("makes", 1),
+            ("jumped", 1),
+            ("don't", 1),
+            ("table", 2),
+            ("little", 2),
+            ("wanted", 2),
+            ("boxes", 2),
+            ("wishes", 2),
+            ("nation", 2),
+            ("social", 2),
+            ("happy", 2),
+            ("Café", 2),
+            ("piano", 3),
+            ("beautiful", 3),
+            ("syllable", 3),
+            ("understanding", 4),
+            ("", 0),
+        ] {
+            assert_eq!(syllables(word), expected, "{word}");
+        }
+    }
+
+    #[test]
+    fn test_scores() {
+        let text = "The cat sat on the mat. It was a sunny day.";
+        let readability = count(&[text]);
+        assert_eq!(readability.words(), 11);
+        assert_eq!(readability.syllables(), 12);
+        assert_eq!(readability.polysyllables(), 0);
+
+        let scores = readability.scores(2).unwrap();
+        assert!((scores.flesch_reading_ease - 108.96).abs() < 0.01);
+        assert!((scores.flesch_kincaid_grade - (-0.57)).abs() < 0.01);
+        assert!((scores.gunning_fog - 2.2).abs() < 0.01);
+        assert!((scores.smog - 3.1291).abs() < 0.01);
+        assert_eq!(count(&["1, 2, 3"]).scores(1), None);
+        assert_eq!(readability.scores(0), None);
+    }
+
+    #[test]
+    fn test_complex_words() {
+        // "interesting" is complex even without its ending, "relaxes" isn't
+        let readability = count(&["An interesting idea relaxes us."]);
+        assert_eq!(readability.polysyllables(), 2);
+        assert_eq!(readability.complex_words, 1);
+    }
+
+    #[test]
+    fn test_across_chunks() {
+        let text = "Naïve readers don't understand beautiful syllables.";
+        let expected = count(&[text]);
+        let boundaries: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+        for &first in &boundaries {
+            for &second in boundaries.iter().filter(|&&i| i >= first) {
+                let readability = count(&[&text[..first], &text[first..second], &text[second..]]);
+                assert_eq!(
+                    (
+                        readability.words,
+                        readability.syllables,
+                        readability.complex_words
+                    ),
+                    (expected.words, expected.syllables, expected.complex_words),
+                    "split at {first} and {second}"
+                );
+            }
+        }
+    }
+}
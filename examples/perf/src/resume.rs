@@ -0,0 +1,177 @@
+//! Merging parsers whose state depends on the text before
+//!
+//! A piece of input counted on its own doesn't know what came before it,
+//! e.g. whether it starts inside a comment. So it's counted as if it
+//! started the input, and keeps its start, its head, to count again once
+//! the text before is known. Past the head, it counted right if the state
+//! after the head is the same either way.
+
+/// Longest head kept before the state at the start of a piece is known.
+/// Beyond that, the rest of the piece is counted as if the piece started
+/// the input, which is usually the case anyway, e.g. outside of comments.
+pub(crate) const MAX_HEAD_LEN: usize = 4096;
+
+/// A parser that counts something in text
+pub(crate) trait Resume: Clone {
+    fn update(&mut self, text: &str);
+
+    /// Count the start of a piece up to where the state no longer depends
+    /// on the text before, if it's in `text`, and return its length.
+    /// Without such a point, the head is only limited by [`MAX_HEAD_LEN`].
+    fn update_head(&mut self, text: &str) -> Option<usize> {
+        self.update(text);
+        None
+    }
+
+    /// Continue with the rest of a piece counted on its own: add what
+    /// `next` counted after its head, where it was at `end`, and take on
+    /// its state
+    fn resume(&mut self, next: Self, end: &Self);
+
+    /// The input has ended
+    fn finish(&mut self) {}
+}
+
+/// A [`Resume`] parser that can be merged with the one counted on the
+/// text that directly follows
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Resumable<P> {
+    /// Counts from the start of the piece, as if it started the input
+    parser: P,
+    /// Text from the start, up to where the state no longer depends on the
+    /// text before, or about `MAX_HEAD_LEN` bytes
+    head: String,
+    /// The parser right after the head, once it's complete
+    head_end: Option<P>,
+}
+
+impl<P: Resume> Resumable<P> {
+    pub(crate) const fn new(parser: P) -> Self {
+        Self {
+            parser,
+            head: String::new(),
+            head_end: None,
+        }
+    }
+
+    pub(crate) const fn get(&self) -> &P {
+        &self.parser
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut P {
+        &mut self.parser
+    }
+
+    pub(crate) fn update(&mut self, text: &str) {
+        if self.head_end.is_some() {
+            self.parser.update(text);
+            return;
+        }
+        let synced = self.parser.update_head(text);
+        let (head, rest) = text.split_at(synced.unwrap_or(text.len()));
+        self.head.push_str(head);
+        if synced.is_some() || self.head.len() > MAX_HEAD_LEN {
+            self.head_end = Some(self.parser.clone());
+        }
+        self.parser.update(rest);
+    }
+
+    pub(crate) fn merge(&mut self, next: Self) {
+        // Count the head of the next piece again, now that the state at its
+        // start is known
+        self.update(&next.head);
+        if let Some(end) = next.head_end {
+            self.head_end.get_or_insert_with(|| self.parser.clone());
+            self.parser.resume(next.parser, &end);
+        }
+    }
+
+    pub(crate) fn finish(&mut self) {
+        self.parser.finish();
+        self.head.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts `#` outside of `/* */` comments
+    #[derive(Debug, Default, Clone)]
+    struct Hashes {
+        in_comment: bool,
+        last: char,
+        count: usize,
+    }
+
+    impl Resume for Hashes {
+        fn update(&mut self, text: &str) {
+            for c in text.chars() {
+                match (self.last, c) {
+                    ('/', '*') => self.in_comment = true,
+                    ('*', '/') => self.in_comment = false,
+                    (_, '#') if !self.in_comment => self.count += 1,
+                    _ => {}
+                }
+                self.last = c;
+            }
+        }
+
+        /// Right after the end of a comment, it's outside of one either way
+        fn update_head(&mut self, text: &str) -> Option<usize> {
+            for (i, c) in text.char_indices() {
+                let last = self.last;
+                self.update(c.encode_utf8(&mut [0; 4]));
+                if (last, c) == ('*', '/') {
+                    return Some(i + 1);
+                }
+            }
+            None
+        }
+
+        fn resume(&mut self, next: Self, end: &Self) {
+            *self = Self {
+                count: self.count + next.count - end.count,
+                ..next
+            };
+        }
+    }
+
+    /// Count `first`, and `second` in chunks of `chunk_size` on its own,
+    /// then merge them
+    fn count(first: &str, second: &str, chunk_size: usize) -> (usize, usize) {
+        let mut merged = Resumable::<Hashes>::default();
+        merged.update(first);
+        let mut next = Resumable::<Hashes>::default();
+        for chunk in second.as_bytes().chunks(chunk_size) {
+            next.update(std::str::from_utf8(chunk).unwrap());
+        }
+        let head = next.head.len();
+        merged.merge(next);
+        merged.finish();
+        (merged.get().count, head)
+    }
+
+    #[test]
+    fn test_synced() {
+        let text = "# /*\n#\n*/ # x # /* #";
+        let (first, second) = text.split_at(4);
+        assert_eq!(count(text, "", 1), (3, 0));
+        assert_eq!(count(first, second, second.len()), (3, "\n#\n*/".len()));
+        assert_eq!(count(first, second, 1), (3, "\n#\n*/".len()));
+    }
+
+    #[test]
+    fn test_long_head() {
+        // The second piece starts in a comment that ends too late. Its head
+        // is counted again, and the state after it is the same either way,
+        // so the rest still counts right.
+        let long = "x\n".repeat(MAX_HEAD_LEN);
+        let text = format!("/*\n#\n{long}*/ # /* {long} #");
+        let (first, second) = text.split_at(2);
+        assert_eq!(count(&text, "", 1).0, 1);
+        let (hashes, head) = count(first, second, MAX_HEAD_LEN + 10);
+        assert_eq!(hashes, 1);
+        assert!(head < second.len());
+    }
+}
@@ -0,0 +1,229 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// How text is split into segments
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Segmentation {
+    /// Extended grapheme clusters, which all count
+    #[default]
+    Graphemes,
+    /// UAX #29 word segments, of which only those with letters or digits
+    /// count
+    Words,
+}
+
+impl Segmentation {
+    /// How many segments at either edge of a chunk might still change once
+    /// the text next to it is known.
+    /// Word boundaries depend on up to two characters of context on either
+    /// side, grapheme cluster boundaries only on one.
+    const fn context(self) -> usize {
+        match self {
+            Self::Graphemes => 1,
+            Self::Words => 2,
+        }
+    }
+
+    /// Start offsets of the segments in `text`
+    fn starts(self, text: &str) -> Box<dyn Iterator<Item = usize> + '_> {
+        match self {
+            Self::Graphemes => Box::new(text.grapheme_indices(true).map(|(i, _)| i)),
+            Self::Words => Box::new(text.split_word_bound_indices().map(|(i, _)| i)),
+        }
+    }
+
+    /// Offset where the last `n` segments of `text` start, or 0 if there
+    /// are no more than `n`
+    fn last(self, text: &str, n: usize) -> usize {
+        match self {
+            Self::Graphemes => text.grapheme_indices(true).nth_back(n - 1),
+            Self::Words => text.split_word_bound_indices().nth_back(n - 1),
+        }
+        .map_or(0, |(i, _)| i)
+    }
+
+    /// Number of segments in `text[..end]` that count, where `end` is a
+    /// segment boundary of `text`.
+    /// Words are also added to `words`, if given.
+    fn count(self, text: &str, end: usize, mut words: Option<&mut Vec<String>>) -> usize {
+        match self {
+            // Fast path: in ASCII, every byte is a grapheme cluster,
+            // except for `\r\n`
+            Self::Graphemes if text.is_ascii() => {
+                let prefix = &text.as_bytes()[..end];
+                end - prefix.windows(2).filter(|w| w == b"\r\n").count()
+            }
+            Self::Graphemes => text
+                .grapheme_indices(true)
+                .take_while(|&(i, _)| i < end)
+                .count(),
+            Self::Words => text
+                .split_word_bound_indices()
+                .take_while(|&(i, _)| i < end)
+                .filter(|(_, segment)| is_word(segment))
+                .inspect(|(_, word)| {
+                    if let Some(words) = words.as_mut() {
+                        words.push((*word).to_string());
+                    }
+                })
+                .count(),
+        }
+    }
+}
+
+/// Like `unicode_words`, only segments with letters or digits are words
+fn is_word(segment: &str) -> bool {
+    segment.chars().any(char::is_alphanumeric)
+}
+
+/// UAX #29 words of `text`, i.e. its segments with letters or digits
+pub(crate) fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split_word_bounds().filter(|segment| is_word(segment))
+}
+
+/// Regional indicator symbols, which make up flag emoji in pairs
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+/// Streaming, mergeable segment counter
+///
+/// Segments at the end of the text seen so far are only counted once more
+/// text arrives or the input ends. For text that was counted on its own
+/// (e.g. on another thread), the same goes for segments at its start,
+/// which can only be settled once it's merged with the text before it.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Segments {
+    kind: Segmentation,
+    /// First segments, which the text before might still change
+    head: String,
+    /// Whether `head` is settled on its right side, so `count` and `tail`
+    /// hold whatever comes after it
+    split: bool,
+    /// Settled segments between `head` and `tail`
+    count: usize,
+    /// Last segments, which the text after might still change
+    tail: String,
+    /// Words settled since they were last taken, if they are recorded
+    words: Option<Vec<String>>,
+}
+
+impl Segments {
+    pub(crate) fn new(kind: Segmentation) -> Self {
+        Self {
+            kind,
+            ..Self::default()
+        }
+    }
+
+    /// Record each word as it's settled, see [`Self::take_words`]
+    pub(crate) fn recording(self) -> Self {
+        Self {
+            words: Some(Vec::new()),
+            ..self
+        }
+    }
+
+    /// Words settled since the last call, if they are recorded
+    pub(crate) fn take_words(&mut self) -> Vec<String> {
+        self.words.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    pub(crate) fn update(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.split && self.kind == Segmentation::Graphemes {
+            // Fast path: in ASCII, every byte is a grapheme cluster,
+            // except for `\r\n`
+            if text.is_ascii() && self.tail.len() <= 1 && self.tail.is_ascii() {
+                let crlf = usize::from(self.tail == "\r" && text.starts_with('\n'))
+                    + text.as_bytes().windows(2).filter(|w| w == b"\r\n").count();
+                self.count += self.tail.len() + text.len() - 1 - crlf;
+                self.tail.clear();
+                self.tail.push_str(&text[text.len() - 1..]);
+                return;
+            }
+        }
+        if self.split {
+            self.tail.push_str(text);
+            self.settle();
+            return;
+        }
+
+        // Split off the head once there's enough text after it.
+        // Regional indicators pair up into flags from the start of a run,
+        // so a run at the start needs to be in the head as a whole.
+        // Only as many segments are looked at as it takes to find the split.
+        self.head.push_str(text);
+        let context = self.kind.context();
+        let mut starts = Vec::new();
+        let split = self.kind.starts(&self.head).find_map(|start| {
+            starts.push(start);
+            // Segment `i` has enough segments before and after it
+            let i = starts
+                .len()
+                .checked_sub(context + 1)
+                .filter(|&i| i >= context)?;
+            let before = &self.head[starts[i - 1]..starts[i]];
+            (!before.chars().any(is_regional_indicator)).then_some(starts[i])
+        });
+        if let Some(split) = split {
+            self.tail = self.head.split_off(split);
+            self.split = true;
+            self.settle();
+        }
+    }
+
+    /// Count all segments of the tail the next chunk can't change anymore
+    fn settle(&mut self) {
+        let keep = self.kind.last(&self.tail, self.kind.context());
+        self.count += self.kind.count(&self.tail, keep, self.words.as_mut());
+        self.tail.drain(..keep);
+    }
+
+    /// Continue with the segments of the text that directly follows
+    pub(crate) fn merge(&mut self, mut next: Self) {
+        if let (Some(words), Some(next)) = (self.words.as_mut(), next.words.as_mut()) {
+            words.append(next);
+        }
+        if !next.split {
+            self.update(&next.head);
+        } else if !self.split {
+            let mut head = std::mem::take(&mut self.head);
+            head.push_str(&next.head);
+            *self = Self {
+                head,
+                words: self.words.take(),
+                ..next
+            };
+        } else {
+            // Both edges of the text in between are settled
+            let mut between = std::mem::take(&mut self.tail);
+            between.push_str(&next.head);
+            self.count += self
+                .kind
+                .count(&between, between.len(), self.words.as_mut())
+                + next.count;
+            self.tail = next.tail;
+        }
+    }
+
+    /// Count the remaining segments at the end of the input
+    pub(crate) fn finish(&mut self) -> usize {
+        self.count += self
+            .kind
+            .count(&self.head, self.head.len(), self.words.as_mut())
+            + self
+                .kind
+                .count(&self.tail, self.tail.len(), self.words.as_mut());
+        self.head.clear();
+        self.tail.clear();
+        self.split = true;
+        self.count
+    }
+
+    /// Segments counted so far
+    pub(crate) const fn count(&self) -> usize {
+        self.count
+    }
+}
@@ -0,0 +1,257 @@
+use std::io::{self, BufRead, ErrorKind, Read};
+
+use regex::Regex;
+
+use crate::markdown::{Blocks, Line};
+
+/// What a line that starts a section looks like
+#[derive(Debug, Clone)]
+pub enum Headings {
+    /// Headings of Markdown, by the same rules as [`Markdown`](crate::Markdown)
+    /// but for HTML comments, which aren't looked into. Setext headings
+    /// start their section at their last line of text, usually their only
+    /// one.
+    Markdown,
+    /// Lines that match a regular expression, like `^\s*CHAPTER [IVXLC]+`
+    Regex(Regex),
+}
+
+impl Headings {
+    pub fn regex(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self::Regex)
+    }
+}
+
+/// Splits text into sections that each start with a heading, and hands
+/// them out one after the other, to be read like files of their own
+///
+/// Text before the first heading is a section without a heading, unless
+/// there is none. Each section is read line by line as it's read, one line
+/// ahead to see setext underlines, so memory only grows with the longest
+/// lines. The text is expected in UTF-8 or another encoding compatible
+/// with ASCII.
+#[derive(Debug)]
+pub struct SectionSplitter<R> {
+    input: R,
+    headings: Headings,
+    /// Where in the blocks of Markdown the next line is
+    blocks: Blocks,
+    /// The current line, of which `start..` is left to read, or nothing at
+    /// the end of the text
+    line: Vec<u8>,
+    start: usize,
+    /// Heading of the section the current line starts, if it does
+    heading: Option<String>,
+    /// The line after the current one, and its heading
+    next: Vec<u8>,
+    next_heading: Option<String>,
+    started: bool,
+}
+
+impl<R: BufRead> SectionSplitter<R> {
+    pub fn new(input: R, headings: Headings) -> Self {
+        Self {
+            input,
+            headings,
+            blocks: Blocks::default(),
+            line: Vec::new(),
+            start: 0,
+            heading: None,
+            next: Vec::new(),
+            next_heading: None,
+            started: false,
+        }
+    }
+
+    /// The next section, or `None` at the end of the text. What's left of
+    /// the previous section is skipped.
+    pub fn next_section(&mut self) -> io::Result<Option<SectionText<'_, R>>> {
+        if self.started {
+            let mut skipped = [0; 4096];
+            while self.read_section(&mut skipped)? > 0 {}
+        } else {
+            self.started = true;
+            // Read the first line ahead, then move on to it
+            self.next_line()?;
+            self.next_line()?;
+            if self.line.is_empty() {
+                return Ok(None);
+            }
+            if self.heading.is_none() {
+                return Ok(Some(SectionText {
+                    heading: None,
+                    splitter: self,
+                }));
+            }
+        }
+        let Some(heading) = self.heading.take() else {
+            return Ok(None);
+        };
+        Ok(Some(SectionText {
+            heading: Some(heading),
+            splitter: self,
+        }))
+    }
+
+    /// Move on to the next line, read the one after it, and tell whether
+    /// either starts a section
+    fn next_line(&mut self) -> io::Result<()> {
+        std::mem::swap(&mut self.line, &mut self.next);
+        self.start = 0;
+        self.heading = self.next_heading.take();
+        self.next.clear();
+        loop {
+            match self.input.read_until(b'\n', &mut self.next) {
+                Ok(_) => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if self.next.is_empty() {
+            return Ok(());
+        }
+        let next = String::from_utf8_lossy(&self.next);
+        let next = next.trim_end_matches(['\n', '\r']);
+        match &self.headings {
+            Headings::Regex(regex) => {
+                if regex.is_match(next) {
+                    self.next_heading = Some(next.trim().to_string());
+                }
+            }
+            Headings::Markdown => match self.blocks.line(next, false) {
+                Line::Heading { .. } => self.next_heading = Some(next.trim().to_string()),
+                Line::Underline { .. } => {
+                    let line = String::from_utf8_lossy(&self.line);
+                    self.heading = Some(line.trim().to_string());
+                }
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    fn read_section(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.heading.is_some() || self.line.is_empty() {
+                return Ok(0);
+            }
+            if self.start < self.line.len() {
+                let len = buf.len().min(self.line.len() - self.start);
+                buf[..len].copy_from_slice(&self.line[self.start..self.start + len]);
+                self.start += len;
+                return Ok(len);
+            }
+            self.next_line()?;
+        }
+    }
+}
+
+/// A section of text, from its heading up to the next one
+#[derive(Debug)]
+pub struct SectionText<'a, R> {
+    heading: Option<String>,
+    splitter: &'a mut SectionSplitter<R>,
+}
+
+impl<R> SectionText<'_, R> {
+    /// The line the section starts with, trimmed, or `None` for the text
+    /// before the first heading
+    pub fn heading(&self) -> Option<&str> {
+        self.heading.as_deref()
+    }
+}
+
+impl<R: BufRead> Read for SectionText<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.splitter.read_section(buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(text: &str, headings: Headings) -> Vec<(Option<String>, String)> {
+        let mut splitter = SectionSplitter::new(text.as_bytes(), headings);
+        let mut sections = Vec::new();
+        while let Some(mut section) = splitter.next_section().unwrap() {
+            let heading = section.heading().map(str::to_string);
+            let mut text = String::new();
+            section.read_to_string(&mut text).unwrap();
+            sections.push((heading, text));
+        }
+        sections
+    }
+
+    fn section(heading: Option<&str>, text: &str) -> (Option<String>, String) {
+        (heading.map(str::to_string), text.to_string())
+    }
+
+    #[test]
+    fn test_regex() {
+        let chapters = Headings::regex(r"^\s*CHAPTER [IVXLC]+").unwrap();
+        let text = "Title\n\n   CHAPTER I\nOnce upon\r\nCHAPTER II\nThe end";
+        assert_eq!(
+            split(text, chapters.clone()),
+            [
+                section(None, "Title\n\n"),
+                section(Some("CHAPTER I"), "   CHAPTER I\nOnce upon\r\n"),
+                section(Some("CHAPTER II"), "CHAPTER II\nThe end"),
+            ]
+        );
+        assert_eq!(
+            split("CHAPTER I\n", chapters.clone()),
+            [section(Some("CHAPTER I"), "CHAPTER I\n")]
+        );
+        assert!(split("", chapters).is_empty());
+        assert!(Headings::regex("(").is_err());
+    }
+
+    #[test]
+    fn test_markdown() {
+        let text = "# Title\nIntro\n```sh\n# not a heading\n```\n#hashtag\n    # indented code\n\
+                    ## Usage\n####### seven\n~~~\n``` not closed\n# still code\n~~~\n   # Last\n";
+        assert_eq!(
+            split(text, Headings::Markdown),
+            [
+                section(
+                    Some("# Title"),
+                    "# Title\nIntro\n```sh\n# not a heading\n```\n#hashtag\n    # indented code\n"
+                ),
+                section(
+                    Some("## Usage"),
+                    "## Usage\n####### seven\n~~~\n``` not closed\n# still code\n~~~\n"
+                ),
+                section(Some("# Last"), "   # Last\n"),
+            ]
+        );
+
+        // Front matter, and setext headings, which start at their text
+        let text =
+            "---\n# yaml comment\n---\nIntro\n\nUsage\r\n=====\nText\n---\n\n---\n- item\n---\n";
+        assert_eq!(
+            split(text, Headings::Markdown),
+            [
+                section(None, "---\n# yaml comment\n---\nIntro\n\n"),
+                section(Some("Usage"), "Usage\r\n=====\n"),
+                section(Some("Text"), "Text\n---\n\n---\n- item\n---\n"),
+            ]
+        );
+        assert_eq!(
+            split("Title\n===", Headings::Markdown),
+            [section(Some("Title"), "Title\n===")]
+        );
+    }
+
+    #[test]
+    fn test_skip() {
+        // Sections that aren't read to the end are skipped
+        let mut splitter = SectionSplitter::new(&b"# A\na\n# B\nb\n"[..], Headings::Markdown);
+        let headings: Vec<String> = std::iter::from_fn(|| {
+            let section = splitter.next_section().unwrap()?;
+            section.heading().map(str::to_string)
+        })
+        .collect();
+        assert_eq!(headings, ["# A", "# B"]);
+    }
+}
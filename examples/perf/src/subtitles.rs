@@ -0,0 +1,467 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read};
+use std::time::Duration;
+
+use crate::metric::{Chunk, Metric};
+use crate::words::{WordBoundary, Words};
+
+/// Longest entity like `&amp;` that is decoded
+const MAX_ENTITY_LEN: usize = 32;
+
+/// Entities of WebVTT cue text
+const ENTITIES: &[(&str, char)] = &[
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("lrm", '\u{200E}'),
+    ("rlm", '\u{200F}'),
+];
+
+/// Blocks of a WebVTT file that aren't cues
+const BLOCKS: &[&str] = &["NOTE", "STYLE", "REGION"];
+
+/// A subtitle shown from `start` to `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cue {
+    pub start: Duration,
+    pub end: Duration,
+    pub words: usize,
+}
+
+impl Cue {
+    pub fn duration(&self) -> Duration {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Reading speed, or `None` if the cue isn't shown at all
+    pub fn words_per_minute(&self) -> Option<f64> {
+        words_per_minute(self.words, self.duration())
+    }
+}
+
+fn words_per_minute(words: usize, duration: Duration) -> Option<f64> {
+    (!duration.is_zero()).then(|| words as f64 * 60.0 / duration.as_secs_f64())
+}
+
+/// Cues of one or more inputs added up
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CueTotals {
+    pub cues: usize,
+    pub words: usize,
+    /// How long the cues are shown, all together
+    pub duration: Duration,
+    /// Number of cues that are read faster than the maximum
+    pub fast: usize,
+}
+
+impl CueTotals {
+    /// Average reading speed over the time the cues are shown
+    pub fn words_per_minute(&self) -> Option<f64> {
+        words_per_minute(self.words, self.duration)
+    }
+}
+
+impl std::ops::AddAssign for CueTotals {
+    fn add_assign(&mut self, other: Self) {
+        self.cues += other.cues;
+        self.words += other.words;
+        self.duration += other.duration;
+        self.fast += other.fast;
+    }
+}
+
+/// Cues of subtitles with their timing and words
+#[derive(Debug, Clone)]
+pub struct Subtitles {
+    /// Reading speed above which a cue is too fast
+    max_words_per_minute: usize,
+    cues: Vec<Cue>,
+    totals: CueTotals,
+}
+
+impl Subtitles {
+    pub const fn new(max_words_per_minute: usize) -> Self {
+        Self {
+            max_words_per_minute,
+            cues: Vec::new(),
+            totals: CueTotals {
+                cues: 0,
+                words: 0,
+                duration: Duration::ZERO,
+                fast: 0,
+            },
+        }
+    }
+
+    /// Cues in the order they were read
+    pub fn cues(&self) -> &[Cue] {
+        &self.cues
+    }
+
+    pub const fn totals(&self) -> CueTotals {
+        self.totals
+    }
+
+    /// Whether a cue is read faster than the maximum, which a cue with
+    /// words that isn't shown at all always is
+    pub fn is_fast(&self, cue: &Cue) -> bool {
+        cue.words_per_minute()
+            .map_or(cue.words > 0, |wpm| wpm > self.max_words_per_minute as f64)
+    }
+
+    fn push(&mut self, cue: Cue) {
+        self.totals += CueTotals {
+            cues: 1,
+            words: cue.words,
+            duration: cue.duration(),
+            fast: usize::from(self.is_fast(&cue)),
+        };
+        self.cues.push(cue);
+    }
+}
+
+/// Add up the totals of separate inputs, e.g. for a total over several
+/// files. The cues themselves are only kept for each input.
+impl std::ops::AddAssign for Subtitles {
+    fn add_assign(&mut self, other: Self) {
+        self.totals += other.totals;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Before the first line, which tells WebVTT from SRT
+    Start,
+    /// The header of a WebVTT file, up to the first blank line
+    Header,
+    /// Between cues, where a cue number or identifier, or the timing of
+    /// the next cue is expected
+    Between,
+    /// A block of a WebVTT file that isn't a cue, like a `NOTE`
+    Block,
+    /// The text of a cue
+    Text,
+}
+
+/// The text of subtitles in SRT or WebVTT format, read from the
+/// subtitles as they're read
+///
+/// Leaves out the header and blocks like `NOTE` of WebVTT, cue numbers
+/// or identifiers, timings with their cue settings, and styling tags like
+/// `<i>`, `<v Speaker>`, or `{\an8}`, and decodes entities like `&amp;`.
+/// Each line of text is kept on its own line. The words of each cue are
+/// counted along with its timing.
+///
+/// The subtitles are expected in UTF-8 or another encoding compatible
+/// with ASCII.
+#[derive(Debug)]
+pub struct SubtitleText<R> {
+    input: BufReader<R>,
+    boundary: WordBoundary,
+    state: State,
+    /// Timing of the cue being read, and the words of its text so far
+    cue: Option<(Duration, Duration, Words)>,
+    subtitles: Subtitles,
+    line: Vec<u8>,
+    /// Text from the subtitles read so far, of which `start..` is left
+    text: Vec<u8>,
+    start: usize,
+}
+
+impl<R: Read> SubtitleText<R> {
+    pub fn new(input: R, boundary: WordBoundary, max_words_per_minute: usize) -> Self {
+        Self {
+            input: BufReader::new(input),
+            boundary,
+            state: State::Start,
+            cue: None,
+            subtitles: Subtitles::new(max_words_per_minute),
+            line: Vec::new(),
+            text: Vec::new(),
+            start: 0,
+        }
+    }
+
+    /// Cues read so far
+    pub const fn subtitles(&self) -> &Subtitles {
+        &self.subtitles
+    }
+
+    pub fn into_subtitles(self) -> Subtitles {
+        self.subtitles
+    }
+
+    fn end_cue(&mut self) {
+        if let Some((start, end, mut words)) = self.cue.take() {
+            words.finish();
+            self.subtitles.push(Cue {
+                start,
+                end,
+                words: words.count(),
+            });
+        }
+    }
+
+    /// Handle a line, without its line break
+    fn add(&mut self, line: &[u8]) {
+        let blank = line.iter().all(u8::is_ascii_whitespace);
+        match self.state {
+            State::Start => {
+                let line = line.strip_prefix("\u{FEFF}".as_bytes()).unwrap_or(line);
+                if is_keyword(line, "WEBVTT") {
+                    self.state = State::Header;
+                } else {
+                    self.state = State::Between;
+                    self.add(line);
+                }
+            }
+            State::Header | State::Block if blank => self.state = State::Between,
+            State::Header | State::Block => {}
+            State::Between => {
+                if let Some((start, end)) = timing(line) {
+                    self.cue = Some((start, end, Words::new(self.boundary)));
+                    self.state = State::Text;
+                } else if BLOCKS.iter().any(|block| is_keyword(line, block)) {
+                    self.state = State::Block;
+                }
+            }
+            State::Text if blank => {
+                self.end_cue();
+                self.state = State::Between;
+            }
+            State::Text => {
+                let start = self.text.len();
+                strip_tags(line, &mut self.text);
+                self.text.push(b'\n');
+                if let Some((_, _, words)) = self.cue.as_mut() {
+                    let text = String::from_utf8_lossy(&self.text[start..]);
+                    words.update(&Chunk::from_text(&text));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for SubtitleText<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.start == self.text.len() {
+            self.text.clear();
+            self.start = 0;
+            let mut line = std::mem::take(&mut self.line);
+            line.clear();
+            match self.input.read_until(b'\n', &mut line) {
+                Ok(0) => {
+                    self.end_cue();
+                    return Ok(0);
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+            let end = line.strip_suffix(b"\n").map_or(line.len(), |line| {
+                line.strip_suffix(b"\r").unwrap_or(line).len()
+            });
+            self.add(&line[..end]);
+            self.line = line;
+        }
+        let len = buf.len().min(self.text.len() - self.start);
+        buf[..len].copy_from_slice(&self.text[self.start..self.start + len]);
+        self.start += len;
+        Ok(len)
+    }
+}
+
+/// Whether a line is a keyword like `WEBVTT`, or starts with it and
+/// whitespace
+fn is_keyword(line: &[u8], keyword: &str) -> bool {
+    line.strip_prefix(keyword.as_bytes())
+        .is_some_and(|rest| rest.first().is_none_or(u8::is_ascii_whitespace))
+}
+
+/// Start and end of a cue from a line like `00:00:01,000 --> 00:00:04,000`,
+/// where WebVTT also allows leaving out the hours, a `.` before the
+/// milliseconds, and cue settings after the end
+fn timing(line: &[u8]) -> Option<(Duration, Duration)> {
+    let (start, end) = std::str::from_utf8(line).ok()?.split_once("-->")?;
+    Some((
+        timestamp(start.trim())?,
+        timestamp(end.split_whitespace().next()?)?,
+    ))
+}
+
+fn timestamp(text: &str) -> Option<Duration> {
+    let (clock, fraction) = text.split_once([',', '.'])?;
+    if fraction.is_empty() || fraction.len() > 3 {
+        return None;
+    }
+    let millis = fraction.parse::<u64>().ok()? * 10u64.pow(3 - fraction.len() as u32);
+    let parts: Vec<u64> = clock
+        .split(':')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (hours, minutes, seconds) = match parts[..] {
+        [minutes, seconds] => (0, minutes, seconds),
+        [hours, minutes, seconds] => (hours, minutes, seconds),
+        _ => return None,
+    };
+    // Timestamps too large for milliseconds in a `u64` don't parse
+    let seconds = hours
+        .checked_mul(60)?
+        .checked_add(minutes)?
+        .checked_mul(60)?
+        .checked_add(seconds)?;
+    Some(Duration::from_millis(
+        seconds.checked_mul(1000)?.checked_add(millis)?,
+    ))
+}
+
+/// Append the text of a line of a cue, without tags like `<i>` or
+/// `{\an8}`, and with its entities decoded
+fn strip_tags(line: &[u8], text: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < line.len() {
+        let rest = &line[i..];
+        let closer = match rest {
+            [b'<', ..] => Some(b'>'),
+            [b'{', b'\\', ..] => Some(b'}'),
+            [b'&', ..] => {
+                if let Some((c, len)) = entity(rest) {
+                    text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    i += len;
+                    continue;
+                }
+                None
+            }
+            _ => None,
+        };
+        // Tags that aren't closed are text
+        match closer.and_then(|closer| rest.iter().position(|&b| b == closer)) {
+            Some(end) => i += end + 1,
+            None => {
+                text.push(line[i]);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// The character of an entity `text` starts with, and its length
+fn entity(text: &[u8]) -> Option<(char, usize)> {
+    let end = text.iter().take(MAX_ENTITY_LEN).position(|&b| b == b';')?;
+    let name = std::str::from_utf8(&text[1..end]).ok()?;
+    let c = if let Some(number) = name.strip_prefix('#') {
+        let code = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => number.parse(),
+        };
+        code.ok()
+            .map(|code| char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))?
+    } else {
+        ENTITIES
+            .iter()
+            .find(|(entity, _)| *entity == name)
+            .map(|&(_, c)| c)?
+    };
+    Some((c, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Trickle;
+
+    fn extract(subtitles: &str) -> (String, Subtitles) {
+        let mut reader = SubtitleText::new(subtitles.as_bytes(), WordBoundary::Ascii, 180);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+
+        let mut trickled = SubtitleText::new(
+            BufReader::with_capacity(1, Trickle(subtitles.as_bytes())),
+            WordBoundary::Ascii,
+            180,
+        );
+        let mut same = String::new();
+        trickled.read_to_string(&mut same).unwrap();
+        assert_eq!(same, text, "{subtitles:?} byte by byte");
+        (text, reader.into_subtitles())
+    }
+
+    fn seconds(seconds: f64) -> Duration {
+        Duration::from_secs_f64(seconds)
+    }
+
+    #[test]
+    fn test_srt() {
+        let (text, subtitles) = extract(
+            "\u{FEFF}1\r\n00:00:01,000 --> 00:00:04,000\r\n<i>Hello</i> there,\r\n\
+             {\\an8}General Kenobi.\r\n\r\n2\r\n00:00:04,500 --> 00:00:05,000\r\n\
+             Far too many words for half a second\r\n",
+        );
+        assert_eq!(
+            text,
+            "Hello there,\nGeneral Kenobi.\nFar too many words for half a second\n"
+        );
+        assert_eq!(
+            subtitles.cues(),
+            [
+                Cue {
+                    start: seconds(1.0),
+                    end: seconds(4.0),
+                    words: 4
+                },
+                Cue {
+                    start: seconds(4.5),
+                    end: seconds(5.0),
+                    words: 8
+                },
+            ]
+        );
+        assert_eq!(subtitles.cues()[0].words_per_minute(), Some(80.0));
+        assert!(!subtitles.is_fast(&subtitles.cues()[0]));
+        assert!(subtitles.is_fast(&subtitles.cues()[1]));
+        let totals = subtitles.totals();
+        assert_eq!((totals.cues, totals.words, totals.fast), (2, 12, 1));
+        assert_eq!(totals.duration, seconds(3.5));
+    }
+
+    #[test]
+    fn test_webvtt() {
+        let (text, subtitles) = extract(
+            "WEBVTT - Some title\nKind: captions\n\nNOTE a comment\nthat goes on\n\n\
+             STYLE\n::cue { color: lime }\n\nintro\n00:01.000 --> 00:03.500 align:start\n\
+             <v Roger>Fish &amp; chips<c.loud>!</c> &lt;3 <00:02.000>OK\n\n\
+             01:00:00.000 --> 01:00:01.000\n&notanentity; 5 &lt 6\n",
+        );
+        assert_eq!(text, "Fish & chips! <3 OK\n&notanentity; 5 &lt 6\n");
+        assert_eq!(
+            subtitles.cues(),
+            [
+                Cue {
+                    start: seconds(1.0),
+                    end: seconds(3.5),
+                    words: 5
+                },
+                Cue {
+                    start: seconds(3600.0),
+                    end: seconds(3601.0),
+                    words: 4
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp("00:00:01,5"), Some(seconds(1.5)));
+        assert_eq!(timestamp("1:02:03.004"), Some(seconds(3723.004)));
+        assert_eq!(timestamp("02:03.004"), Some(seconds(123.004)));
+        assert_eq!(timestamp("02:03"), None);
+        assert_eq!(timestamp("1:2:3:4.000"), None);
+        assert_eq!(timing(b"00:01.000 -> 00:02.000"), None);
+        assert_eq!(timestamp("18446744073709551615:00.000"), None);
+        assert_eq!(timestamp("18446744073709551:00.000"), None);
+        assert_eq!(timestamp("0:18446744073709551615.000"), None);
+    }
+}
@@ -0,0 +1,20 @@
+//! Helpers shared by the tests of several modules
+
+use std::io::{self, Read};
+
+/// Reader that hands out its input one byte per `read` call, to split every
+/// token, field or line across reads
+pub(crate) struct Trickle<'a>(pub(crate) &'a [u8]);
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.split_first() {
+            Some((&byte, rest)) if !buf.is_empty() => {
+                buf[0] = byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
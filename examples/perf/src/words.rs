@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use crate::kernel::Kernel;
+use crate::metric::{Chunk, Metric};
+use crate::segments::{Segmentation, Segments};
+
+/// What separates one word from the next
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum WordBoundary {
+    /// ASCII whitespace only, like `wc` in the C locale
+    #[default]
+    Ascii,
+    /// Any character with the Unicode `White_Space` property,
+    /// e.g. a no-break space or U+3000 ideographic space
+    Unicode,
+    /// Unicode word segmentation (UAX #29).
+    /// Punctuation is not part of any word, and CJK ideographs are
+    /// words on their own.
+    Uax29,
+}
+
+impl FromStr for WordBoundary {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ascii" => Ok(Self::Ascii),
+            "unicode" => Ok(Self::Unicode),
+            "uax29" => Ok(Self::Uax29),
+            _ => Err(format!(
+                "Unknown word boundary: {s} (expected ascii, unicode, or uax29)"
+            )),
+        }
+    }
+}
+
+/// Number of words
+#[derive(Debug, Clone)]
+pub struct Words {
+    boundary: WordBoundary,
+    /// Fastest kernel for ASCII word boundaries on this CPU
+    kernel: Kernel,
+    count: usize,
+    /// Whether the first character is part of a word, or `None` if there
+    /// was no input yet
+    starts_in_word: Option<bool>,
+    /// Whether the last character seen so far is part of a word
+    in_word: bool,
+    /// UAX #29 segments whose boundaries aren't settled yet
+    segments: Segments,
+}
+
+impl Default for Words {
+    fn default() -> Self {
+        Self::new(WordBoundary::default())
+    }
+}
+
+impl Words {
+    pub fn new(boundary: WordBoundary) -> Self {
+        Self {
+            boundary,
+            kernel: Kernel::detect(),
+            count: 0,
+            starts_in_word: None,
+            in_word: false,
+            segments: Segments::new(Segmentation::Words),
+        }
+    }
+
+    /// Words counted so far. With UAX #29 boundaries, the last few are only
+    /// counted once the metric is finished.
+    pub const fn count(&self) -> usize {
+        match self.boundary {
+            WordBoundary::Uax29 => self.segments.count(),
+            WordBoundary::Ascii | WordBoundary::Unicode => self.count,
+        }
+    }
+
+    /// Like [`Metric::update`], and also return the newlines in the chunk if
+    /// the ASCII kernel counted them on the way, so that
+    /// [`Lines::update_counted`] doesn't need to look for them again
+    ///
+    /// [`Lines::update_counted`]: crate::metric::Lines::update_counted
+    pub fn update_counting_lines(&mut self, chunk: &Chunk<'_>) -> Option<usize> {
+        match (self.boundary, chunk.text) {
+            (WordBoundary::Ascii, _) => {
+                let Some(&first) = chunk.bytes.first() else {
+                    return Some(0);
+                };
+                self.starts_in_word
+                    .get_or_insert(!first.is_ascii_whitespace());
+                let ascii = self.kernel.count(chunk.bytes, self.in_word);
+                self.count += ascii.words;
+                self.in_word = ascii.in_word;
+                return Some(ascii.lines);
+            }
+            (WordBoundary::Unicode, Some(text)) => {
+                for c in text.chars() {
+                    self.step(c.is_whitespace());
+                }
+            }
+            (WordBoundary::Uax29, Some(text)) => self.segments.update(text),
+            (_, None) => {}
+        }
+        None
+    }
+
+    fn step(&mut self, is_separator: bool) {
+        self.starts_in_word.get_or_insert(!is_separator);
+        if is_separator {
+            self.in_word = false;
+        } else if !self.in_word {
+            self.count += 1;
+            self.in_word = true;
+        }
+    }
+}
+
+impl Metric for Words {
+    /// ASCII whitespace can be found in the raw bytes
+    fn needs_text(&self) -> bool {
+        self.boundary != WordBoundary::Ascii
+    }
+
+    fn update(&mut self, chunk: &Chunk<'_>) {
+        self.update_counting_lines(chunk);
+    }
+
+    fn finish(&mut self) {
+        self.segments.finish();
+    }
+
+    fn merge(&mut self, next: Self) {
+        let Some(starts_in_word) = next.starts_in_word else {
+            self.segments.merge(next.segments);
+            return;
+        };
+        // A word going on across the two pieces was counted in both
+        self.count += next.count - usize::from(self.in_word && starts_in_word);
+        self.starts_in_word.get_or_insert(starts_in_word);
+        self.in_word = next.in_word;
+        self.segments.merge(next.segments);
+    }
+}